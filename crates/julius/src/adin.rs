@@ -1,6 +1,96 @@
+mod resample;
+mod sample;
+
+pub use resample::Resampler;
+pub use sample::{ChannelMode, InputFormat, Sample, SampleConverter, I24};
+
+/// Return value of a custom ADIn read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdinRead<U> {
+    /// Samples read. Empty when no samples are available yet, libjulius then reads again.
+    Samples(U),
+    /// Ends the current segment. Recognition continues with the next samples.
+    EndOfSegment,
+    EndOfStream,
+    Error,
+}
+
+impl<U> From<Option<U>> for AdinRead<U> {
+    fn from(value: Option<U>) -> Self {
+        match value {
+            Some(data) => Self::Samples(data),
+            None => Self::EndOfStream,
+        }
+    }
+}
+
+/// Audio input decoded on the Rust side, fed through the custom ADIn path.
+pub trait AudioSource {
+    type Sample: Sample;
+    fn format(&self) -> InputFormat;
+    fn sample_rate(&self) -> u32;
+    /// Reads up to `max` interleaved samples.
+    fn read(&mut self, max: usize) -> AdinRead<Vec<Self::Sample>>;
+}
+
+impl<A: AudioSource + ?Sized> AudioSource for Box<A> {
+    type Sample = A::Sample;
+    fn format(&self) -> InputFormat {
+        (**self).format()
+    }
+    fn sample_rate(&self) -> u32 {
+        (**self).sample_rate()
+    }
+    fn read(&mut self, max: usize) -> AdinRead<Vec<Self::Sample>> {
+        (**self).read(max)
+    }
+}
+
 pub struct ADIn(libjulius_sys::ADIn);
 
 impl ADIn {
+    /// Number of samples captured since the stream was opened.
+    pub fn total_captured_len(&self) -> u64 {
+        self.0.total_captured_len as u64
+    }
+    /// Position of the last trigger in the captured samples.
+    ///
+    /// Without the built-in detection, the custom ADIn path sets this to the first sample of each
+    /// segment ended by [`AdinRead::EndOfSegment`].
+    pub fn last_trigger_sample(&self) -> u64 {
+        self.0.last_trigger_sample as u64
+    }
+    /// Length of the last triggered input in samples.
+    pub fn last_trigger_len(&self) -> u64 {
+        self.0.last_trigger_len as u64
+    }
+    /// Level threshold of the built-in detection (`-lv`).
+    pub fn level_threshold(&self) -> i32 {
+        self.0.thres
+    }
+    pub(crate) fn set_level_threshold(&mut self, thres: i32) {
+        self.0.thres = thres;
+    }
+    /// Number of zero crosses within the head margin needed to trigger (`-zc` scaled by `-headmargin`).
+    pub fn noise_zerocross(&self) -> i32 {
+        self.0.noise_zerocross
+    }
+    pub(crate) fn set_noise_zerocross(&mut self, num: i32) {
+        self.0.noise_zerocross = num;
+    }
+    /// Whether the built-in level and zero-cross detection cuts the input.
+    pub fn is_silence_cut(&self) -> bool {
+        self.0.adin_cut_on != 0
+    }
+    pub(crate) fn set_silence_cut(&mut self, on: bool) {
+        self.0.adin_cut_on = on as libjulius_sys::boolean;
+    }
+    /// Marks the next injected sample as the start of a new segment.
+    pub(crate) fn start_injected_segment(&mut self) {
+        if self.0.adin_cut_on == 0 {
+            self.0.last_trigger_sample = self.0.total_captured_len;
+        }
+    }
     pub(crate) fn samp_num(&self) -> i32 {
         self.0.bpmax - self.0.bp
     }
@@ -17,19 +107,22 @@ impl ADIn {
         self.0.enable_thread = 0;
         self.0.down_sample = 0;
     }
-    pub(crate) fn ad_read_inject_callback<T: AsRef<[i16]>>(&mut self, data: Option<T>) -> i32 {
-        if let Some(data) = data {
-            let cnt = data.as_ref().len();
-            assert!(cnt as i32 <= self.samp_num());
-            unsafe {
-                self.0
-                    .buffer
-                    .offset(self.0.bp as isize)
-                    .copy_from(data.as_ref().as_ptr(), cnt);
+    pub(crate) fn ad_read_inject_callback<T: AsRef<[i16]>>(&mut self, data: AdinRead<T>) -> i32 {
+        match data {
+            AdinRead::Samples(data) => {
+                let cnt = data.as_ref().len();
+                assert!(cnt as i32 <= self.samp_num());
+                unsafe {
+                    self.0
+                        .buffer
+                        .offset(self.0.bp as isize)
+                        .copy_from(data.as_ref().as_ptr(), cnt);
+                }
+                cnt as i32
             }
-            cnt as i32
-        } else {
-            -1
+            AdinRead::EndOfStream => -1,
+            AdinRead::Error => -2,
+            AdinRead::EndOfSegment => -3,
         }
     }
     extern "C" fn dummy_ad_read(_buf: *mut libjulius_sys::SP16, _sampnum: i32) -> i32 {
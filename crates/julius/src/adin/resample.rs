@@ -0,0 +1,216 @@
+use std::f64::consts::PI;
+
+/// Number of filter taps on each side of the kernel at unity cutoff.
+const HALF_TAPS: f64 = 16.0;
+
+/// Streaming band-limited resampler for mono `SP16` audio.
+///
+/// Uses a Blackman-windowed sinc kernel, so arbitrary rate pairs (e.g. 44.1kHz to 16kHz) are
+/// supported. Output sample `n` corresponds to input time `n * input_rate / output_rate`, so
+/// the timeline is preserved.
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    input_rate: u32,
+    output_rate: u32,
+    /// Normalized cutoff frequency relative to the input Nyquist frequency.
+    cutoff: f64,
+    /// Kernel half width in input samples.
+    half_width: usize,
+    buffer: Vec<f32>,
+    /// Position of the next output sample in `buffer`, in units of `1 / output_rate` input
+    /// samples so that it does not drift.
+    pos: u64,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32) -> Result<Self, anyhow::Error> {
+        if input_rate == 0 || output_rate == 0 {
+            return Err(anyhow::anyhow!("Sampling rate must not be zero"));
+        }
+        let step = input_rate as f64 / output_rate as f64;
+        let cutoff = if step > 1.0 { 0.95 / step } else { 0.95 };
+        let half_width = (HALF_TAPS / cutoff).ceil() as usize;
+        Ok(Self {
+            input_rate,
+            output_rate,
+            cutoff,
+            half_width,
+            buffer: vec![0.0; half_width],
+            pos: half_width as u64 * output_rate as u64,
+        })
+    }
+
+    pub fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
+    pub fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+    pub fn is_passthrough(&self) -> bool {
+        self.input_rate == self.output_rate
+    }
+
+    /// Resamples `input` and appends the result to `out`.
+    ///
+    /// Up to `half_width` input samples are held back until enough lookahead is available.
+    pub fn process(&mut self, input: &[i16], out: &mut Vec<i16>) {
+        if self.is_passthrough() {
+            out.extend_from_slice(input);
+            return;
+        }
+        self.buffer.extend(input.iter().map(|&s| s as f32));
+        self.drain(out);
+    }
+
+    /// Flushes the samples held back by [`Resampler::process`].
+    pub fn flush(&mut self, out: &mut Vec<i16>) {
+        if self.is_passthrough() {
+            return;
+        }
+        let end = self.buffer.len() as u64 * self.output_rate as u64;
+        self.buffer
+            .extend(std::iter::repeat_n(0.0, self.half_width + 1));
+        while self.pos < end {
+            out.push(self.interpolate());
+            self.pos += self.input_rate as u64;
+        }
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.buffer.resize(self.half_width, 0.0);
+        self.pos = self.half_width as u64 * self.output_rate as u64;
+    }
+
+    fn drain(&mut self, out: &mut Vec<i16>) {
+        let output_rate = self.output_rate as u64;
+        let end = self.buffer.len().saturating_sub(self.half_width) as u64 * output_rate;
+        while self.pos < end {
+            out.push(self.interpolate());
+            self.pos += self.input_rate as u64;
+        }
+        let consumed = ((self.pos / output_rate) as usize).saturating_sub(self.half_width);
+        if consumed > 0 {
+            self.buffer.drain(..consumed);
+            self.pos -= consumed as u64 * output_rate;
+        }
+    }
+
+    fn interpolate(&self) -> i16 {
+        let center = (self.pos / self.output_rate as u64) as isize;
+        let pos =
+            center as f64 + (self.pos % self.output_rate as u64) as f64 / self.output_rate as f64;
+        let first = (center - self.half_width as isize + 1).max(0) as usize;
+        let last = ((center + self.half_width as isize) as usize).min(self.buffer.len() - 1);
+        let value: f64 = (first..=last)
+            .map(|i| self.buffer[i] as f64 * self.kernel(pos - i as f64))
+            .sum();
+        value.round().clamp(i16::MIN as f64, i16::MAX as f64) as i16
+    }
+
+    fn kernel(&self, x: f64) -> f64 {
+        let width = self.half_width as f64;
+        if x.abs() >= width {
+            return 0.0;
+        }
+        let t = x * self.cutoff;
+        let sinc = if t == 0.0 {
+            1.0
+        } else {
+            (PI * t).sin() / (PI * t)
+        };
+        let w = PI * (x / width + 1.0);
+        let window = 0.42 - 0.5 * w.cos() + 0.08 * (2.0 * w).cos();
+        self.cutoff * sinc * window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATES: [u32; 4] = [8000, 22050, 44100, 48000];
+
+    fn resample(input_rate: u32, input: &[i16], chunk: usize) -> (Vec<i16>, usize) {
+        let mut resampler = Resampler::new(input_rate, 16000).unwrap();
+        let mut out = vec![];
+        for chunk in input.chunks(chunk) {
+            resampler.process(chunk, &mut out);
+        }
+        let processed = out.len();
+        resampler.flush(&mut out);
+        (out, processed)
+    }
+
+    #[test]
+    fn output_length_follows_rate_ratio() {
+        for rate in RATES {
+            let input = vec![0; rate as usize];
+            let (out, _) = resample(rate, &input, 160);
+            assert_eq!(out.len(), 16000, "{} Hz", rate);
+
+            let input = vec![0; 12345];
+            let expected = (12345 * 16000_u64).div_ceil(rate as u64) as usize;
+            let (out, _) = resample(rate, &input, 1000);
+            assert_eq!(out.len(), expected, "{} Hz", rate);
+        }
+    }
+
+    #[test]
+    fn chunking_does_not_change_output() {
+        let input: Vec<i16> = (0..4410).map(|i| ((i * 37) % 2000 - 1000) as i16).collect();
+        for rate in RATES {
+            let (whole, _) = resample(rate, &input, input.len());
+            let (chunked, _) = resample(rate, &input, 7);
+            assert_eq!(whole, chunked, "{} Hz", rate);
+        }
+    }
+
+    #[test]
+    fn dc_gain_is_one() {
+        for rate in RATES {
+            let input = vec![10000; rate as usize / 10];
+            let (out, _) = resample(rate, &input, 512);
+            // Skip the edges, where the kernel reaches past the input
+            for &s in &out[100..out.len() - 100] {
+                assert!((s - 10000).abs() <= 10, "{} Hz: {}", rate, s);
+            }
+        }
+    }
+
+    #[test]
+    fn flush_emits_held_back_tail() {
+        for rate in RATES {
+            let input = vec![10000; 4000];
+            let (out, processed) = resample(rate, &input, 4000);
+            let expected = (4000 * 16000_u64).div_ceil(rate as u64) as usize;
+            assert!(processed < expected, "{} Hz", rate);
+            assert_eq!(out.len(), expected, "{} Hz", rate);
+            // The first held-back sample barely reaches the zero padding
+            assert!((out[processed] - 10000).abs() <= 10, "{} Hz", rate);
+        }
+    }
+
+    #[test]
+    fn flush_resets_timeline() {
+        let mut resampler = Resampler::new(48000, 16000).unwrap();
+        let mut first = vec![];
+        resampler.process(&[1000; 480], &mut first);
+        resampler.flush(&mut first);
+        let mut second = vec![];
+        resampler.process(&[1000; 480], &mut second);
+        resampler.flush(&mut second);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn passthrough_keeps_samples() {
+        let mut resampler = Resampler::new(16000, 16000).unwrap();
+        let mut out = vec![];
+        resampler.process(&[1, 2, 3], &mut out);
+        resampler.flush(&mut out);
+        assert_eq!(out, [1, 2, 3]);
+        assert!(Resampler::new(0, 16000).is_err());
+    }
+}
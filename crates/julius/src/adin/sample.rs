@@ -0,0 +1,294 @@
+use std::fmt::Debug;
+
+/// Sample type accepted by the custom ADIn path.
+///
+/// Samples are converted to `SP16` (16-bit signed integer) before being injected into libjulius.
+/// The conversion rounds to the nearest value, ties away from zero, and saturates.
+pub trait Sample: Copy + Debug {
+    /// Returns the sample normalized to `[-1.0, 1.0)`.
+    fn to_f32(self) -> f32;
+    /// Returns the sample scaled into the `SP16` range.
+    fn to_sp16(self) -> i16 {
+        f32_to_sp16(self.to_f32())
+    }
+}
+
+fn f32_to_sp16(value: f32) -> i16 {
+    (value * 32768.0)
+        .round()
+        .clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+/// Drops the lowest `shift` bits with the same rounding as [`f32_to_sp16`].
+fn shift_to_sp16(value: i32, shift: u32) -> i16 {
+    let value = value as i64;
+    let half = 1 << (shift - 1);
+    let scaled = if value < 0 {
+        -((half - value) >> shift)
+    } else {
+        (value + half) >> shift
+    };
+    scaled.clamp(i16::MIN as i64, i16::MAX as i64) as i16
+}
+
+impl Sample for i16 {
+    fn to_f32(self) -> f32 {
+        self as f32 / 32768.0
+    }
+    fn to_sp16(self) -> i16 {
+        self
+    }
+}
+
+impl Sample for f32 {
+    fn to_f32(self) -> f32 {
+        self
+    }
+}
+
+impl Sample for i32 {
+    fn to_f32(self) -> f32 {
+        self as f32 / 2147483648.0
+    }
+    fn to_sp16(self) -> i16 {
+        shift_to_sp16(self, 16)
+    }
+}
+
+impl Sample for u8 {
+    fn to_f32(self) -> f32 {
+        (self as f32 - 128.0) / 128.0
+    }
+    fn to_sp16(self) -> i16 {
+        (self as i16 - 128) << 8
+    }
+}
+
+/// 24-bit signed integer sample, stored sign-extended in an `i32`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct I24(i32);
+
+impl I24 {
+    pub const MIN: i32 = -(1 << 23);
+    pub const MAX: i32 = (1 << 23) - 1;
+
+    /// Creates a sample, saturating `value` into the 24-bit range.
+    pub fn new(value: i32) -> Self {
+        Self(value.clamp(Self::MIN, Self::MAX))
+    }
+    pub fn from_le_bytes(bytes: [u8; 3]) -> Self {
+        Self(i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8)
+    }
+    pub fn from_be_bytes(bytes: [u8; 3]) -> Self {
+        Self(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], 0]) >> 8)
+    }
+    pub fn get(self) -> i32 {
+        self.0
+    }
+}
+
+impl Sample for I24 {
+    fn to_f32(self) -> f32 {
+        self.0 as f32 / 8388608.0
+    }
+    fn to_sp16(self) -> i16 {
+        shift_to_sp16(self.0, 8)
+    }
+}
+
+/// How multi-channel input is reduced to the single channel libjulius decodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelMode {
+    /// Average all channels.
+    #[default]
+    Mix,
+    /// Use only the channel at the given index.
+    Select(usize),
+}
+
+/// Layout of the samples given to the custom ADIn path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputFormat {
+    /// Number of interleaved channels.
+    pub channels: usize,
+    pub channel_mode: ChannelMode,
+}
+
+impl Default for InputFormat {
+    fn default() -> Self {
+        Self::mono()
+    }
+}
+
+impl InputFormat {
+    pub fn mono() -> Self {
+        Self {
+            channels: 1,
+            channel_mode: ChannelMode::Mix,
+        }
+    }
+    pub fn interleaved(channels: usize, channel_mode: ChannelMode) -> Self {
+        Self {
+            channels,
+            channel_mode,
+        }
+    }
+}
+
+/// Converts interleaved samples of any [`Sample`] type into mono `SP16`.
+///
+/// Incomplete frames at the end of a chunk are kept until the next call.
+#[derive(Debug)]
+pub struct SampleConverter<S: Sample> {
+    format: InputFormat,
+    partial: Vec<S>,
+}
+
+impl<S: Sample> SampleConverter<S> {
+    pub fn new(format: InputFormat) -> Result<Self, anyhow::Error> {
+        if format.channels == 0 {
+            return Err(anyhow::anyhow!("Number of channels must not be zero"));
+        }
+        if let ChannelMode::Select(ch) = format.channel_mode {
+            if ch >= format.channels {
+                return Err(anyhow::anyhow!(
+                    "Channel {} is out of range for {}-channel input",
+                    ch,
+                    format.channels
+                ));
+            }
+        }
+        Ok(Self {
+            format,
+            partial: Vec::with_capacity(format.channels),
+        })
+    }
+
+    pub fn format(&self) -> InputFormat {
+        self.format
+    }
+
+    /// Converts `samples` and appends the result to `out`.
+    pub fn convert(&mut self, samples: &[S], out: &mut Vec<i16>) {
+        let channels = self.format.channels;
+        let mut samples = samples;
+
+        if !self.partial.is_empty() {
+            let needed = (channels - self.partial.len()).min(samples.len());
+            self.partial.extend_from_slice(&samples[..needed]);
+            samples = &samples[needed..];
+            if self.partial.len() < channels {
+                return;
+            }
+            out.push(self.convert_frame(&self.partial));
+            self.partial.clear();
+        }
+
+        let mut frames = samples.chunks_exact(channels);
+        out.extend(frames.by_ref().map(|frame| self.convert_frame(frame)));
+        self.partial.extend_from_slice(frames.remainder());
+    }
+
+    fn convert_frame(&self, frame: &[S]) -> i16 {
+        match self.format.channel_mode {
+            ChannelMode::Select(ch) => frame[ch].to_sp16(),
+            ChannelMode::Mix if frame.len() == 1 => frame[0].to_sp16(),
+            ChannelMode::Mix => {
+                let sum: f32 = frame.iter().map(|s| s.to_f32()).sum();
+                f32_to_sp16(sum / frame.len() as f32)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert<S: Sample>(format: InputFormat, samples: &[S]) -> Vec<i16> {
+        let mut out = vec![];
+        SampleConverter::new(format)
+            .unwrap()
+            .convert(samples, &mut out);
+        out
+    }
+
+    #[test]
+    fn scales_into_sp16() {
+        let mono = InputFormat::mono();
+        assert_eq!(
+            convert(mono, &[i16::MIN, -1, 0, i16::MAX]),
+            [i16::MIN, -1, 0, i16::MAX]
+        );
+        assert_eq!(
+            convert(mono, &[0u8, 127, 128, 255]),
+            [i16::MIN, -256, 0, 32512]
+        );
+        assert_eq!(
+            convert(mono, &[i32::MIN, -0x18000, 0x7fff, 0x8000, i32::MAX]),
+            [i16::MIN, -2, 0, 1, i16::MAX]
+        );
+        assert_eq!(
+            convert(
+                mono,
+                &[
+                    I24::new(I24::MIN),
+                    I24::new(-0x180),
+                    I24::new(0x80),
+                    I24::new(I24::MAX)
+                ]
+            ),
+            [i16::MIN, -2, 1, i16::MAX]
+        );
+        assert_eq!(
+            convert(mono, &[-1.5f32, -1.0, 0.5, 1.0]),
+            [i16::MIN, i16::MIN, 16384, i16::MAX]
+        );
+    }
+
+    #[test]
+    fn integer_and_float_paths_round_alike() {
+        for value in [
+            -0x18000, -0x8000, -0x7fff, 0x7fff, 0x8000, 0x18000, 0x12345678,
+        ] {
+            let float = f32_to_sp16(value as f32 / 2147483648.0);
+            assert_eq!(value.to_sp16(), float, "{:#x}", value);
+        }
+    }
+
+    #[test]
+    fn mixes_channels() {
+        let stereo = InputFormat::interleaved(2, ChannelMode::Mix);
+        assert_eq!(
+            convert(stereo, &[100i16, 200, -100, -201, i16::MAX, i16::MAX]),
+            [150, -151, i16::MAX]
+        );
+        assert_eq!(convert(stereo, &[1.0f32, -1.0, 0.5, 0.5]), [0, 16384]);
+    }
+
+    #[test]
+    fn selects_channel() {
+        let format = InputFormat::interleaved(3, ChannelMode::Select(1));
+        assert_eq!(convert(format, &[1i16, 2, 3, 4, 5, 6]), [2, 5]);
+        assert!(
+            SampleConverter::<i16>::new(InputFormat::interleaved(2, ChannelMode::Select(2)))
+                .is_err()
+        );
+        assert!(
+            SampleConverter::<i16>::new(InputFormat::interleaved(0, ChannelMode::Mix)).is_err()
+        );
+    }
+
+    #[test]
+    fn keeps_incomplete_frames() {
+        let mut converter =
+            SampleConverter::new(InputFormat::interleaved(2, ChannelMode::Select(1))).unwrap();
+        let mut out = vec![];
+        converter.convert(&[1i16, 2, 3], &mut out);
+        assert_eq!(out, [2]);
+        converter.convert(&[4], &mut out);
+        assert_eq!(out, [2, 4]);
+        converter.convert(&[5, 6, 7, 8], &mut out);
+        assert_eq!(out, [2, 4, 6, 8]);
+    }
+}
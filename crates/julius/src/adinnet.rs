@@ -0,0 +1,423 @@
+//! Server of Julius' adinnet protocol (`-input adinnet`), receiving audio from clients such as
+//! `adintool -out adinnet`.
+//!
+//! Every message is a 4-byte signed length followed by that many bytes of `SP16` samples. Both
+//! are little-endian, as Julius and adintool send them on little-endian hosts. An empty message
+//! ends the current segment and a negative length ends the stream. The server sends one-byte
+//! messages back to pause (`0`), resume (`1`) or terminate (`2`) the client.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::adin::{AdinRead, AudioSource, InputFormat};
+
+/// Default port of `-adport`.
+pub const DEFAULT_PORT: u16 = 5530;
+
+/// Upper bound of a message, to reject broken clients before allocating.
+const MAX_MESSAGE_LEN: usize = 1 << 24;
+
+/// How long a read waits for a client or data before returning no samples, so that libjulius
+/// gets to handle requests such as those of [`RecogControl`](crate::control::RecogControl).
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Connection and segment changes of an [`AdinNetServer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdinNetEvent {
+    Connected(SocketAddr),
+    /// The client ended a segment with an empty message.
+    EndOfSegment,
+    /// The client ended the stream with a negative length.
+    EndOfStream,
+    /// The client disconnected or sent a broken message. The server waits for the next one.
+    Disconnected,
+}
+
+enum Message {
+    Audio(Vec<u8>),
+    EndOfSegment,
+    EndOfStream,
+}
+
+/// [`AudioSource`] receiving audio from one adinnet client at a time.
+///
+/// Clients are accepted while reading. Reads return no samples while waiting for a client or
+/// data. When the client disconnects, an unfinished segment ends and the server waits for the
+/// next client, so recognition goes on.
+pub struct AdinNetServer {
+    listener: TcpListener,
+    sample_rate: u32,
+    client: Option<TcpStream>,
+    control: AdinNetControl,
+    /// Bytes received and not yet parsed into messages.
+    received: Vec<u8>,
+    /// Last byte of an odd-length message, completed by the next one.
+    odd_byte: Option<u8>,
+    samples: VecDeque<i16>,
+    /// Whether audio has been received since the last end of segment.
+    in_segment: bool,
+    on_event: Option<Box<dyn FnMut(AdinNetEvent)>>,
+}
+
+impl AdinNetServer {
+    /// Listens on `addr` for clients sending audio at `sample_rate`.
+    pub fn bind<A: ToSocketAddrs>(addr: A, sample_rate: u32) -> Result<Self, anyhow::Error> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            sample_rate,
+            client: None,
+            control: AdinNetControl::default(),
+            received: Vec::new(),
+            odd_byte: None,
+            samples: VecDeque::new(),
+            in_segment: false,
+            on_event: None,
+        })
+    }
+
+    /// Sets a callback receiving [`AdinNetEvent`]s as they happen.
+    pub fn with_callback<F: FnMut(AdinNetEvent) + 'static>(mut self, on_event: F) -> Self {
+        self.on_event = Some(Box::new(on_event));
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, anyhow::Error> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Returns a handle to send commands to the connected client.
+    pub fn control(&self) -> AdinNetControl {
+        self.control.clone()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.client.is_some()
+    }
+
+    /// Waits for a client unless one is connected, and returns its address.
+    pub fn accept(&mut self) -> Result<SocketAddr, anyhow::Error> {
+        loop {
+            if let Some(client) = &self.client {
+                return Ok(client.peer_addr()?);
+            }
+            if self.try_accept()?.is_none() {
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+
+    fn try_accept(&mut self) -> io::Result<Option<SocketAddr>> {
+        let (client, addr) = match self.listener.accept() {
+            Ok(accepted) => accepted,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        client.set_nonblocking(false)?;
+        client.set_nodelay(true)?;
+        client.set_read_timeout(Some(POLL_INTERVAL))?;
+        *self.control.lock() = Some(client.try_clone()?);
+        self.client = Some(client);
+        self.received.clear();
+        self.odd_byte = None;
+        self.emit(AdinNetEvent::Connected(addr));
+        Ok(Some(addr))
+    }
+
+    /// Drops the client, ending its unfinished segment.
+    fn disconnect(&mut self) -> AdinRead<Vec<i16>> {
+        self.client = None;
+        *self.control.lock() = None;
+        self.emit(AdinNetEvent::Disconnected);
+        self.end_segment()
+    }
+
+    fn end_segment(&mut self) -> AdinRead<Vec<i16>> {
+        self.odd_byte = None;
+        if std::mem::take(&mut self.in_segment) {
+            AdinRead::EndOfSegment
+        } else {
+            AdinRead::Samples(Vec::new())
+        }
+    }
+
+    fn emit(&mut self, event: AdinNetEvent) {
+        if let Some(on_event) = &mut self.on_event {
+            on_event(event);
+        }
+    }
+
+    /// Takes a complete message from the received bytes.
+    fn parse_message(&mut self) -> io::Result<Option<Message>> {
+        let Some(header) = self.received.first_chunk::<4>() else {
+            return Ok(None);
+        };
+        let Ok(len) = usize::try_from(i32::from_le_bytes(*header)) else {
+            self.received.drain(..4);
+            return Ok(Some(Message::EndOfStream));
+        };
+        if len > MAX_MESSAGE_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid adinnet message length {}", len),
+            ));
+        }
+        if self.received.len() < 4 + len {
+            return Ok(None);
+        }
+        let message: Vec<u8> = self.received.drain(..4 + len).skip(4).collect();
+        Ok(Some(if message.is_empty() {
+            Message::EndOfSegment
+        } else {
+            Message::Audio(message)
+        }))
+    }
+
+    /// Reads from the client until a message is complete or the poll interval elapses.
+    fn receive(&mut self) -> io::Result<Option<Message>> {
+        if let Some(message) = self.parse_message()? {
+            return Ok(Some(message));
+        }
+        let Some(client) = &mut self.client else {
+            return Ok(None);
+        };
+        let mut buf = [0; 4096];
+        match client.read(&mut buf) {
+            Ok(0) => Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                self.received.extend_from_slice(&buf[..n]);
+                self.parse_message()
+            }
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn push_audio(&mut self, mut bytes: Vec<u8>) {
+        if let Some(byte) = self.odd_byte.take() {
+            bytes.insert(0, byte);
+        }
+        if !bytes.len().is_multiple_of(2) {
+            self.odd_byte = bytes.pop();
+        }
+        self.samples.extend(
+            bytes
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]])),
+        );
+        self.in_segment = true;
+    }
+}
+
+impl AudioSource for AdinNetServer {
+    type Sample = i16;
+    fn format(&self) -> InputFormat {
+        InputFormat::mono()
+    }
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    fn read(&mut self, max: usize) -> AdinRead<Vec<i16>> {
+        if self.samples.is_empty() {
+            if self.client.is_none() {
+                match self.try_accept() {
+                    Ok(Some(_)) => {}
+                    Ok(None) => {
+                        std::thread::sleep(POLL_INTERVAL);
+                        return AdinRead::Samples(Vec::new());
+                    }
+                    Err(_) => return AdinRead::Error,
+                }
+            }
+            match self.receive() {
+                Ok(Some(Message::Audio(bytes))) => self.push_audio(bytes),
+                Ok(Some(Message::EndOfSegment)) => {
+                    self.emit(AdinNetEvent::EndOfSegment);
+                    self.odd_byte = None;
+                    self.in_segment = false;
+                    return AdinRead::EndOfSegment;
+                }
+                Ok(Some(Message::EndOfStream)) => {
+                    self.emit(AdinNetEvent::EndOfStream);
+                    self.odd_byte = None;
+                    self.in_segment = false;
+                    return AdinRead::EndOfStream;
+                }
+                Ok(None) => return AdinRead::Samples(Vec::new()),
+                Err(_) => return self.disconnect(),
+            }
+        }
+        let len = max.min(self.samples.len());
+        AdinRead::Samples(self.samples.drain(..len).collect())
+    }
+}
+
+/// Handle to send the commands of `adin_tcpip_send_pause`, `adin_tcpip_send_resume` and
+/// `adin_tcpip_send_terminate` to the client of an [`AdinNetServer`].
+#[derive(Debug, Clone, Default)]
+pub struct AdinNetControl(Arc<Mutex<Option<TcpStream>>>);
+
+impl AdinNetControl {
+    /// Asks the client to stop sending after the current segment.
+    pub fn pause(&self) -> Result<(), anyhow::Error> {
+        self.send(b'0')
+    }
+
+    /// Asks the client to resume sending.
+    pub fn resume(&self) -> Result<(), anyhow::Error> {
+        self.send(b'1')
+    }
+
+    /// Asks the client to discard the current segment and stop sending.
+    pub fn terminate(&self) -> Result<(), anyhow::Error> {
+        self.send(b'2')
+    }
+
+    fn send(&self, command: u8) -> Result<(), anyhow::Error> {
+        let mut client = self.lock();
+        let client = client
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("No adinnet client is connected"))?;
+        let mut message = 1i32.to_le_bytes().to_vec();
+        message.push(command);
+        client.write_all(&message)?;
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<TcpStream>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    fn send(client: &mut TcpStream, bytes: &[u8]) {
+        client
+            .write_all(&(bytes.len() as i32).to_le_bytes())
+            .unwrap();
+        client.write_all(bytes).unwrap();
+    }
+
+    /// Reads until `last` is returned, merging consecutive samples.
+    fn read_until(server: &mut AdinNetServer, last: AdinRead<Vec<i16>>) -> Vec<AdinRead<Vec<i16>>> {
+        let mut reads: Vec<AdinRead<Vec<i16>>> = vec![];
+        for _ in 0..100 {
+            let read = server.read(3);
+            let done = read == last;
+            match (reads.last_mut(), read) {
+                (_, AdinRead::Samples(samples)) if samples.is_empty() => {}
+                (Some(AdinRead::Samples(prev)), AdinRead::Samples(samples)) => prev.extend(samples),
+                (_, read) => reads.push(read),
+            }
+            if done {
+                return reads;
+            }
+        }
+        panic!("{:?} was not read, got {:?}", last, reads);
+    }
+
+    #[test]
+    fn receives_audio_and_sends_commands() {
+        let events = Rc::new(RefCell::new(vec![]));
+        let e = events.clone();
+        let mut server = AdinNetServer::bind("127.0.0.1:0", 16000)
+            .unwrap()
+            .with_callback(move |event| e.borrow_mut().push(event));
+        let addr = server.local_addr().unwrap();
+
+        // No client yet
+        assert_eq!(server.read(10), AdinRead::Samples(vec![]));
+        assert!(!server.is_connected());
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        // Odd-length messages continue in the next one
+        send(&mut client, &[1, 0, 2, 0, 3]);
+        send(&mut client, &[0, 4, 0, 0xff]);
+        send(&mut client, &[0xff]);
+        send(&mut client, &[]);
+        client.write_all(&(-1i32).to_le_bytes()).unwrap();
+        assert_eq!(
+            read_until(&mut server, AdinRead::EndOfStream),
+            [
+                AdinRead::Samples(vec![1, 2, 3, 4, -1]),
+                AdinRead::EndOfSegment,
+                AdinRead::EndOfStream
+            ]
+        );
+
+        let control = server.control();
+        control.pause().unwrap();
+        control.resume().unwrap();
+        control.terminate().unwrap();
+        let mut commands = [0; 15];
+        client.read_exact(&mut commands).unwrap();
+        assert_eq!(
+            commands,
+            [1, 0, 0, 0, b'0', 1, 0, 0, 0, b'1', 1, 0, 0, 0, b'2']
+        );
+
+        // A disconnect ends the unfinished segment, then the next client is served
+        send(&mut client, &[5, 0]);
+        drop(client);
+        assert_eq!(
+            read_until(&mut server, AdinRead::EndOfSegment),
+            [AdinRead::Samples(vec![5]), AdinRead::EndOfSegment]
+        );
+        assert!(!server.is_connected());
+        assert!(control.pause().is_err());
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        send(&mut client, &[6, 0]);
+        send(&mut client, &[]);
+        assert_eq!(
+            read_until(&mut server, AdinRead::EndOfSegment),
+            [AdinRead::Samples(vec![6]), AdinRead::EndOfSegment]
+        );
+
+        let events = events.borrow();
+        assert!(matches!(events[0], AdinNetEvent::Connected(_)));
+        assert_eq!(
+            events[1..4],
+            [
+                AdinNetEvent::EndOfSegment,
+                AdinNetEvent::EndOfStream,
+                AdinNetEvent::Disconnected
+            ]
+        );
+        assert!(matches!(events[4], AdinNetEvent::Connected(_)));
+        assert_eq!(events[5..], [AdinNetEvent::EndOfSegment]);
+    }
+
+    #[test]
+    fn drops_client_sending_broken_message() {
+        let mut server = AdinNetServer::bind("127.0.0.1:0", 16000).unwrap();
+        let mut client = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        client
+            .write_all(&(MAX_MESSAGE_LEN as i32 + 1).to_le_bytes())
+            .unwrap();
+        for _ in 0..100 {
+            server.read(10);
+            if !server.is_connected() {
+                return;
+            }
+        }
+        panic!("The client was not dropped");
+    }
+}
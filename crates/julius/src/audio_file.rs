@@ -0,0 +1,418 @@
+//! Audio file input decoded in Rust, without libsndfile.
+//!
+//! Every source yields full-scale `i32` samples, which are fed through
+//! [`Recog::custom_adin_source`](crate::Recog::custom_adin_source).
+
+#[cfg(any(feature = "wav", feature = "flac"))]
+use std::fs::File;
+#[cfg(feature = "wav")]
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::adin::AudioSource;
+#[cfg(any(feature = "wav", feature = "flac"))]
+use crate::adin::{AdinRead, ChannelMode, InputFormat};
+
+/// Opens an audio file, choosing the decoder by extension.
+pub fn open<P: AsRef<Path>>(path: P) -> Result<Box<dyn AudioSource<Sample = i32>>, anyhow::Error> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+    match extension.as_deref() {
+        #[cfg(feature = "wav")]
+        Some("wav") => Ok(Box::new(WavSource::open(path)?)),
+        #[cfg(feature = "flac")]
+        Some("flac") => Ok(Box::new(FlacSource::open(path)?)),
+        _ => Err(anyhow::anyhow!(
+            "Unsupported audio file: {}",
+            path.display()
+        )),
+    }
+}
+
+#[cfg(any(feature = "wav", feature = "flac"))]
+fn scale_int(value: i32, bits: u32) -> i32 {
+    value << (32 - bits)
+}
+
+#[cfg(feature = "wav")]
+enum WavSamples<R: std::io::Read> {
+    Int(hound::WavIntoSamples<R, i32>, u32),
+    Float(hound::WavIntoSamples<R, f32>),
+}
+
+/// WAV file source supporting integer PCM and 32-bit float samples.
+#[cfg(feature = "wav")]
+pub struct WavSource<R: std::io::Read = BufReader<File>> {
+    spec: hound::WavSpec,
+    channel_mode: ChannelMode,
+    samples: WavSamples<R>,
+}
+
+#[cfg(feature = "wav")]
+impl WavSource<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, anyhow::Error> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(feature = "wav")]
+impl<R: std::io::Read> WavSource<R> {
+    pub fn new(reader: R) -> Result<Self, anyhow::Error> {
+        let reader = hound::WavReader::new(reader)?;
+        let spec = reader.spec();
+        let samples = match spec.sample_format {
+            hound::SampleFormat::Int => {
+                WavSamples::Int(reader.into_samples(), spec.bits_per_sample as u32)
+            }
+            hound::SampleFormat::Float => WavSamples::Float(reader.into_samples()),
+        };
+        Ok(Self {
+            spec,
+            channel_mode: ChannelMode::Mix,
+            samples,
+        })
+    }
+    pub fn with_channel_mode(mut self, channel_mode: ChannelMode) -> Self {
+        self.channel_mode = channel_mode;
+        self
+    }
+}
+
+#[cfg(feature = "wav")]
+impl<R: std::io::Read> AudioSource for WavSource<R> {
+    type Sample = i32;
+    fn format(&self) -> InputFormat {
+        InputFormat::interleaved(self.spec.channels as usize, self.channel_mode)
+    }
+    fn sample_rate(&self) -> u32 {
+        self.spec.sample_rate
+    }
+    fn read(&mut self, max: usize) -> AdinRead<Vec<i32>> {
+        let samples: Result<Vec<i32>, hound::Error> = match &mut self.samples {
+            WavSamples::Int(samples, bits) => samples
+                .take(max)
+                .map(|s| s.map(|s| scale_int(s, *bits)))
+                .collect(),
+            WavSamples::Float(samples) => samples
+                .take(max)
+                .map(|s| {
+                    s.map(|s| {
+                        (s as f64 * 2147483648.0).clamp(i32::MIN as f64, i32::MAX as f64) as i32
+                    })
+                })
+                .collect(),
+        };
+        match samples {
+            Ok(samples) if samples.is_empty() => AdinRead::EndOfStream,
+            Ok(samples) => AdinRead::Samples(samples),
+            Err(e) => {
+                crate::jlog::error(format_args!("WavSource: {}", e));
+                AdinRead::Error
+            }
+        }
+    }
+}
+
+/// FLAC file source.
+#[cfg(feature = "flac")]
+pub struct FlacSource<R: std::io::Read = File> {
+    reader: claxon::FlacReader<R>,
+    channel_mode: ChannelMode,
+    buffer: Vec<i32>,
+    pending: Vec<i32>,
+}
+
+#[cfg(feature = "flac")]
+impl FlacSource<File> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, anyhow::Error> {
+        Self::new(File::open(path)?)
+    }
+}
+
+#[cfg(feature = "flac")]
+impl<R: std::io::Read> FlacSource<R> {
+    pub fn new(reader: R) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            reader: claxon::FlacReader::new(reader)?,
+            channel_mode: ChannelMode::Mix,
+            buffer: Vec::new(),
+            pending: Vec::new(),
+        })
+    }
+    pub fn with_channel_mode(mut self, channel_mode: ChannelMode) -> Self {
+        self.channel_mode = channel_mode;
+        self
+    }
+}
+
+#[cfg(feature = "flac")]
+impl<R: std::io::Read> AudioSource for FlacSource<R> {
+    type Sample = i32;
+    fn format(&self) -> InputFormat {
+        InputFormat::interleaved(
+            self.reader.streaminfo().channels as usize,
+            self.channel_mode,
+        )
+    }
+    fn sample_rate(&self) -> u32 {
+        self.reader.streaminfo().sample_rate
+    }
+    fn read(&mut self, max: usize) -> AdinRead<Vec<i32>> {
+        if self.pending.is_empty() {
+            let bits = self.reader.streaminfo().bits_per_sample;
+            let buffer = std::mem::take(&mut self.buffer);
+            match self.reader.blocks().read_next_or_eof(buffer) {
+                Ok(Some(block)) => {
+                    // Blocks are stored channel by channel
+                    for i in 0..block.duration() {
+                        for ch in 0..block.channels() {
+                            self.pending.push(scale_int(block.sample(ch, i), bits));
+                        }
+                    }
+                    self.buffer = block.into_buffer();
+                }
+                Ok(None) => return AdinRead::EndOfStream,
+                Err(e) => {
+                    crate::jlog::error(format_args!("FlacSource: {}", e));
+                    return AdinRead::Error;
+                }
+            }
+        }
+        let len = max.min(self.pending.len());
+        AdinRead::Samples(self.pending.drain(..len).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(feature = "wav", feature = "flac"))]
+    use super::*;
+
+    /// Reads `source` to the end in reads of `max` samples.
+    #[cfg(any(feature = "wav", feature = "flac"))]
+    fn read_all(source: &mut impl AudioSource<Sample = i32>, max: usize) -> Vec<i32> {
+        let mut samples = vec![];
+        loop {
+            match source.read(max) {
+                AdinRead::Samples(chunk) => {
+                    assert!(chunk.len() <= max);
+                    samples.extend(chunk);
+                }
+                AdinRead::EndOfStream => return samples,
+                other => panic!("unexpected read: {:?}", other),
+            }
+        }
+    }
+
+    #[cfg(feature = "wav")]
+    fn wav<S: hound::Sample + Copy>(spec: hound::WavSpec, samples: &[S]) -> Vec<u8> {
+        let mut bytes = std::io::Cursor::new(vec![]);
+        let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+        for &sample in samples {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        bytes.into_inner()
+    }
+
+    #[cfg(feature = "wav")]
+    fn int_spec(channels: u16, bits: u16) -> hound::WavSpec {
+        hound::WavSpec {
+            channels,
+            sample_rate: 16000,
+            bits_per_sample: bits,
+            sample_format: hound::SampleFormat::Int,
+        }
+    }
+
+    #[cfg(feature = "wav")]
+    #[test]
+    fn scales_wav_integers_to_full_scale() {
+        let read = |bytes: Vec<u8>| read_all(&mut WavSource::new(&bytes[..]).unwrap(), 3);
+
+        let bytes = wav(int_spec(1, 8), &[i8::MIN, -1, 0, 1, i8::MAX]);
+        assert_eq!(read(bytes), [i32::MIN, -1 << 24, 0, 1 << 24, 0x7f00_0000]);
+        let bytes = wav(int_spec(1, 16), &[i16::MIN, -1, 0, i16::MAX]);
+        assert_eq!(read(bytes), [i32::MIN, -1 << 16, 0, 0x7fff_0000]);
+        let bytes = wav(int_spec(1, 24), &[-0x80_0000, -1, 0x7f_ffff]);
+        assert_eq!(read(bytes), [i32::MIN, -1 << 8, 0x7fff_ff00]);
+        let bytes = wav(int_spec(1, 32), &[i32::MIN, -1, i32::MAX]);
+        assert_eq!(read(bytes), [i32::MIN, -1, i32::MAX]);
+    }
+
+    #[cfg(feature = "wav")]
+    #[test]
+    fn clamps_wav_floats() {
+        let spec = hound::WavSpec {
+            sample_format: hound::SampleFormat::Float,
+            ..int_spec(1, 32)
+        };
+        let bytes = wav(spec, &[-2.0f32, -1.0, -0.5, 0.0, 0.5, 1.0, 2.0]);
+        let mut source = WavSource::new(&bytes[..]).unwrap();
+        assert_eq!(source.sample_rate(), 16000);
+        assert_eq!(
+            read_all(&mut source, 4),
+            [
+                i32::MIN,
+                i32::MIN,
+                -0x4000_0000,
+                0,
+                0x4000_0000,
+                i32::MAX,
+                i32::MAX
+            ]
+        );
+    }
+
+    #[cfg(feature = "wav")]
+    #[test]
+    fn keeps_wav_channels_interleaved() {
+        use crate::adin::SampleConverter;
+
+        let bytes = wav(int_spec(2, 16), &[100i16, -100, 200, -200, 300, -300]);
+        let mut source = WavSource::new(&bytes[..]).unwrap();
+        assert_eq!(
+            source.format(),
+            InputFormat::interleaved(2, ChannelMode::Mix)
+        );
+        let samples = read_all(&mut source, 3);
+        assert_eq!(samples.len(), 6);
+
+        let mut source = WavSource::new(&bytes[..])
+            .unwrap()
+            .with_channel_mode(ChannelMode::Select(1));
+        let format = source.format();
+        assert_eq!(format, InputFormat::interleaved(2, ChannelMode::Select(1)));
+        let mut mono = vec![];
+        SampleConverter::new(format)
+            .unwrap()
+            .convert(&read_all(&mut source, 3), &mut mono);
+        assert_eq!(mono, [-100, -200, -300]);
+
+        let mut mixed = vec![];
+        SampleConverter::new(InputFormat::interleaved(2, ChannelMode::Mix))
+            .unwrap()
+            .convert(&samples, &mut mixed);
+        assert_eq!(mixed, [0, 0, 0]);
+    }
+
+    /// Writes bits most significant first.
+    #[cfg(feature = "flac")]
+    #[derive(Default)]
+    struct Bits {
+        bytes: Vec<u8>,
+        len: usize,
+    }
+
+    #[cfg(feature = "flac")]
+    impl Bits {
+        fn push(&mut self, value: u64, bits: usize) {
+            for i in (0..bits).rev() {
+                if self.len.is_multiple_of(8) {
+                    self.bytes.push(0);
+                }
+                if value >> i & 1 == 1 {
+                    *self.bytes.last_mut().unwrap() |= 0x80 >> (self.len % 8);
+                }
+                self.len += 1;
+            }
+        }
+    }
+
+    #[cfg(feature = "flac")]
+    fn crc(bytes: &[u8], poly: u16, width: u32) -> u16 {
+        let top = 1 << (width - 1);
+        let mask = ((1u32 << width) - 1) as u16;
+        let mut crc = 0u16;
+        for &byte in bytes {
+            crc ^= (byte as u16) << (width - 8);
+            for _ in 0..8 {
+                crc = if crc & top != 0 {
+                    (crc << 1) ^ poly
+                } else {
+                    crc << 1
+                } & mask;
+            }
+        }
+        crc
+    }
+
+    /// FLAC file of 16-bit samples in verbatim subframes, one frame per block.
+    #[cfg(feature = "flac")]
+    fn flac(sample_rate: u32, blocks: &[Vec<Vec<i16>>]) -> Vec<u8> {
+        let channels = blocks[0].len() as u64;
+        let total: usize = blocks.iter().map(|block| block[0].len()).sum();
+        let mut bits = Bits::default();
+        bits.push(u32::from_be_bytes(*b"fLaC") as u64, 32);
+        // Last metadata block, STREAMINFO of 34 bytes
+        bits.push(1, 1);
+        bits.push(0, 7);
+        bits.push(34, 24);
+        bits.push(16, 16);
+        bits.push(blocks[0][0].len() as u64, 16);
+        bits.push(0, 48);
+        bits.push(sample_rate as u64, 20);
+        bits.push(channels - 1, 3);
+        bits.push(15, 5);
+        bits.push(total as u64, 36);
+        // No MD5 signature
+        bits.push(0, 64);
+        bits.push(0, 64);
+
+        for (number, block) in blocks.iter().enumerate() {
+            let start = bits.bytes.len();
+            bits.push(0b11111111111110, 14);
+            bits.push(0, 2);
+            // Block size in 16 bits after the header, the sampling rate from STREAMINFO,
+            // independent channels of 16 bits
+            bits.push(0b0111, 4);
+            bits.push(0, 4);
+            bits.push(channels - 1, 4);
+            bits.push(0b1000, 4);
+            bits.push(number as u64, 8);
+            bits.push(block[0].len() as u64 - 1, 16);
+            let crc8 = crc(&bits.bytes[start..], 0x07, 8);
+            bits.push(crc8 as u64, 8);
+            for channel in block {
+                bits.push(0b00000010, 8);
+                for &sample in channel {
+                    bits.push(sample as u16 as u64, 16);
+                }
+            }
+            let crc16 = crc(&bits.bytes[start..], 0x8005, 16);
+            bits.len = bits.bytes.len() * 8;
+            bits.push(crc16 as u64, 16);
+        }
+        bits.bytes
+    }
+
+    #[cfg(feature = "flac")]
+    #[test]
+    fn interleaves_flac_blocks() {
+        let left: Vec<i16> = (0..20).map(|i| i * 100).collect();
+        let right: Vec<i16> = (0..20).map(|i| -i * 100 - 1).collect();
+        let blocks = [
+            vec![left[..16].to_vec(), right[..16].to_vec()],
+            vec![left[16..].to_vec(), right[16..].to_vec()],
+        ];
+        let bytes = flac(16000, &blocks);
+
+        let mut source = FlacSource::new(&bytes[..])
+            .unwrap()
+            .with_channel_mode(ChannelMode::Select(1));
+        assert_eq!(source.sample_rate(), 16000);
+        assert_eq!(
+            source.format(),
+            InputFormat::interleaved(2, ChannelMode::Select(1))
+        );
+        let expected: Vec<i32> = left
+            .iter()
+            .zip(&right)
+            .flat_map(|(&l, &r)| [(l as i32) << 16, (r as i32) << 16])
+            .collect();
+        assert_eq!(read_all(&mut source, 7), expected);
+    }
+}
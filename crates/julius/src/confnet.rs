@@ -0,0 +1,184 @@
+//! Confusion network (word sausage) results.
+
+use libjulius_sys::WORD_ID;
+
+use crate::recog_process::RecogProcess;
+
+/// Confusion network of the 2nd pass, available on
+/// [`CallbackType::ResultConfnet`](crate::CallbackType::ResultConfnet) when `-confnet` is enabled.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConfusionNetwork {
+    pub slots: Vec<ConfusionSlot>,
+}
+
+/// Competing words at one position of a [`ConfusionNetwork`].
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConfusionSlot {
+    pub words: Vec<ConfusionWord>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConfusionWord {
+    /// Word ID in the dictionary, or `None` for a skip (no word at this position).
+    pub word_id: Option<WORD_ID>,
+    /// Output string of the word, empty for a skip.
+    pub word: String,
+    /// Posterior probability.
+    pub posterior: f32,
+}
+
+impl ConfusionNetwork {
+    /// Copies the confusion network of the latest result of `process`.
+    pub fn from_process(process: &RecogProcess) -> Option<Self> {
+        Self::from_clusters(process.result().0.confnet, |w| {
+            process.word_output(w).and_then(Result::ok)
+        })
+    }
+
+    /// Copies the list of clusters starting at `first`.
+    fn from_clusters(
+        first: *mut libjulius_sys::CN_CLUSTER,
+        word_output: impl Fn(WORD_ID) -> Option<String>,
+    ) -> Option<Self> {
+        let mut current = first;
+        let mut slots = Vec::new();
+        while let Some(cluster) = unsafe { current.as_ref() } {
+            let num = cluster.wordsnum.max(0) as usize;
+            let words = (0..num)
+                .map(|i| {
+                    let (w, pp) = unsafe { (*cluster.words.add(i), *cluster.pp.add(i)) };
+                    let word_id = (w != libjulius_sys::WORD_INVALID as WORD_ID).then_some(w);
+                    ConfusionWord {
+                        word_id,
+                        word: word_id.and_then(&word_output).unwrap_or_default(),
+                        posterior: pp,
+                    }
+                })
+                .collect();
+            slots.push(ConfusionSlot { words });
+            current = cluster.next;
+        }
+        if slots.is_empty() {
+            None
+        } else {
+            Some(Self { slots })
+        }
+    }
+
+    /// Most probable word of every slot, leaving out skips.
+    pub fn best_path(&self) -> impl Iterator<Item = &ConfusionWord> {
+        self.slots
+            .iter()
+            .filter_map(|s| s.best())
+            .filter(|w| w.word_id.is_some())
+    }
+}
+
+impl ConfusionSlot {
+    /// Word with the highest posterior.
+    pub fn best(&self) -> Option<&ConfusionWord> {
+        self.words
+            .iter()
+            .max_by(|a, b| a.posterior.total_cmp(&b.posterior))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::null_mut;
+
+    use super::*;
+
+    const SKIP: WORD_ID = libjulius_sys::WORD_INVALID as WORD_ID;
+
+    /// Builds the cluster list of `slots` and copies it.
+    fn build(slots: &[&[(WORD_ID, f32)]]) -> Option<ConfusionNetwork> {
+        let mut words: Vec<Vec<WORD_ID>> = slots
+            .iter()
+            .map(|s| s.iter().map(|&(w, _)| w).collect())
+            .collect();
+        let mut pp: Vec<Vec<f32>> = slots
+            .iter()
+            .map(|s| s.iter().map(|&(_, p)| p).collect())
+            .collect();
+        let mut clusters: Vec<libjulius_sys::CN_CLUSTER> = words
+            .iter_mut()
+            .zip(&mut pp)
+            .map(|(words, pp)| {
+                let mut cluster: libjulius_sys::CN_CLUSTER = unsafe { std::mem::zeroed() };
+                cluster.words = words.as_mut_ptr();
+                cluster.pp = pp.as_mut_ptr();
+                cluster.wordsnum = words.len() as i32;
+                cluster
+            })
+            .collect();
+        let first = clusters.as_mut_ptr();
+        for i in 1..clusters.len() {
+            clusters[i - 1].next = unsafe { first.add(i) };
+        }
+        let first = if clusters.is_empty() {
+            null_mut()
+        } else {
+            first
+        };
+        ConfusionNetwork::from_clusters(first, |w| Some(format!("w{}", w)))
+    }
+
+    fn sample() -> ConfusionNetwork {
+        build(&[
+            &[(0, 0.9), (1, 0.1)],
+            &[(SKIP, 0.6), (2, 0.4)],
+            &[(3, 0.3), (4, 0.7)],
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn copies_clusters() {
+        let network = sample();
+        assert_eq!(network.slots.len(), 3);
+        assert_eq!(
+            network.slots[1].words,
+            [
+                ConfusionWord {
+                    word_id: None,
+                    word: String::new(),
+                    posterior: 0.6
+                },
+                ConfusionWord {
+                    word_id: Some(2),
+                    word: "w2".to_string(),
+                    posterior: 0.4
+                }
+            ]
+        );
+        assert_eq!(build(&[]), None);
+    }
+
+    #[test]
+    fn best_path_leaves_out_skips() {
+        let network = sample();
+        let best: Vec<_> = network
+            .slots
+            .iter()
+            .map(|s| s.best().unwrap().word_id)
+            .collect();
+        assert_eq!(best, [Some(0), None, Some(4)]);
+        let path: Vec<_> = network.best_path().map(|w| w.word.as_str()).collect();
+        assert_eq!(path, ["w0", "w4"]);
+        assert_eq!(ConfusionSlot::default().best(), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn round_trips_through_serde() {
+        let network = sample();
+        let json = serde_json::to_string(&network).unwrap();
+        assert!(json.contains(r#"{"word_id":null,"word":"","posterior":0.6}"#));
+        let read: ConfusionNetwork = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, network);
+    }
+}
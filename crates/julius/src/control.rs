@@ -0,0 +1,254 @@
+//! Controlling a running recognizer from other threads.
+
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard, Weak},
+    time::Duration,
+};
+
+use crate::{CallbackType, Recog};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Request {
+    Pause,
+    Terminate,
+}
+
+/// Pointer to the controlled recognizer, cleared by [`detach`] before it is freed.
+#[derive(Debug)]
+struct RecogPtr(*mut libjulius_sys::Recog);
+
+// Only dereferenced with the state lock held.
+unsafe impl Send for RecogPtr {}
+
+#[derive(Debug, Default)]
+struct State {
+    recog: Option<RecogPtr>,
+    /// Request passed to libjulius and not yet resumed.
+    requested: Option<Request>,
+    resume: bool,
+    paused: bool,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+/// Handles of live recognizers, keyed by address.
+static HANDLES: Mutex<Vec<(usize, Weak<Shared>)>> = Mutex::new(Vec::new());
+
+/// Disconnects the handles of `recog`, which is about to be freed.
+pub(crate) fn detach(recog: &libjulius_sys::Recog) {
+    let addr = recog as *const _ as usize;
+    let mut handles = HANDLES.lock().unwrap_or_else(|e| e.into_inner());
+    handles.retain(|(a, shared)| {
+        if *a != addr {
+            return shared.strong_count() > 0;
+        }
+        if let Some(shared) = shared.upgrade() {
+            let mut state = shared.lock();
+            state.recog = None;
+            state.paused = false;
+            // Wake up the waiters, which would never be notified otherwise
+            shared.cond.notify_all();
+        }
+        false
+    });
+}
+
+/// Handle to pause, resume or terminate recognition from any thread.
+///
+/// Requests are passed to libjulius right away, as Julius' module mode does from its command
+/// thread. libjulius acts on them while it reads audio input, then recognition pauses until
+/// [`RecogControl::resume`] is called. Requests made after the [`Recog`] is dropped are ignored,
+/// and waiting fails from then on.
+#[derive(Debug, Clone)]
+pub struct RecogControl(Arc<Shared>);
+
+impl RecogControl {
+    /// Creates a handle for `recog`.
+    ///
+    /// This registers the [`CallbackType::PauseFunction`], [`CallbackType::EventPause`] and
+    /// [`CallbackType::EventResume`] callbacks, so it should be called once before recognition
+    /// and the handle cloned as needed.
+    pub fn new(recog: &mut Recog) -> Self {
+        let shared = Shared::register(unsafe { recog.as_raw_ptr() });
+
+        let s = shared.clone();
+        recog.add_callback(CallbackType::EventPause, move |_| {
+            s.lock().paused = true;
+            s.cond.notify_all();
+        });
+        let s = shared.clone();
+        recog.add_callback(CallbackType::PauseFunction, move |recog| {
+            let mut state = s
+                .cond
+                .wait_while(s.lock(), |state| !state.resume)
+                .unwrap_or_else(|e| e.into_inner());
+            state.resume = false;
+            state.requested = None;
+            unsafe { libjulius_sys::j_request_resume(&mut *recog.0) };
+        });
+        let s = shared.clone();
+        recog.add_callback(CallbackType::EventResume, move |_| {
+            s.lock().paused = false;
+            s.cond.notify_all();
+        });
+
+        Self(shared)
+    }
+
+    /// Pauses after the current input has been recognized.
+    pub fn pause(&self) {
+        self.request(Request::Pause);
+    }
+
+    /// Pauses immediately, discarding the current input.
+    ///
+    /// This overrides a pending [`RecogControl::pause`].
+    pub fn terminate(&self) {
+        self.request(Request::Terminate);
+    }
+
+    fn request(&self, request: Request) {
+        let mut state = self.0.lock();
+        // A pause would clear a pending terminate in libjulius, while a terminate overrides
+        // a pending pause
+        if state.paused
+            || state
+                .requested
+                .is_some_and(|r| r == request || r == Request::Terminate)
+        {
+            return;
+        }
+        let Some(recog) = &state.recog else {
+            return;
+        };
+        unsafe {
+            match request {
+                Request::Pause => libjulius_sys::j_request_pause(recog.0),
+                Request::Terminate => libjulius_sys::j_request_terminate(recog.0),
+            }
+        }
+        state.requested = Some(request);
+    }
+
+    /// Resumes a paused recognition, or cancels a pending request.
+    ///
+    /// A cancelled request still pauses libjulius briefly, as it cannot be withdrawn.
+    pub fn resume(&self) {
+        let mut state = self.0.lock();
+        if state.requested.is_some() || state.paused {
+            state.resume = true;
+            self.0.cond.notify_all();
+        }
+    }
+
+    /// Returns whether recognition is paused, i.e. [`CallbackType::EventPause`] has been emitted.
+    pub fn is_paused(&self) -> bool {
+        self.0.lock().paused
+    }
+
+    /// Blocks until recognition pauses, and returns whether it paused. Returns `false` when the
+    /// [`Recog`] is dropped first.
+    pub fn wait_paused(&self) -> bool {
+        self.wait(true, None)
+    }
+
+    /// Blocks until recognition pauses or `timeout` elapses, and returns whether it paused.
+    pub fn wait_paused_timeout(&self, timeout: Duration) -> bool {
+        self.wait(true, Some(timeout))
+    }
+
+    /// Blocks until recognition resumes, and returns whether it resumed. Returns `false` when the
+    /// [`Recog`] is dropped first.
+    pub fn wait_resumed(&self) -> bool {
+        self.wait(false, None)
+    }
+
+    /// Blocks until recognition resumes or `timeout` elapses, and returns whether it resumed.
+    pub fn wait_resumed_timeout(&self, timeout: Duration) -> bool {
+        self.wait(false, Some(timeout))
+    }
+
+    fn wait(&self, paused: bool, timeout: Option<Duration>) -> bool {
+        let state = self.0.lock();
+        let condition = |state: &mut State| state.recog.is_some() && state.paused != paused;
+        let state = match timeout {
+            Some(timeout) => {
+                self.0
+                    .cond
+                    .wait_timeout_while(state, timeout, condition)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0
+            }
+            None => self
+                .0
+                .cond
+                .wait_while(state, condition)
+                .unwrap_or_else(|e| e.into_inner()),
+        };
+        state.recog.is_some() && state.paused == paused
+    }
+}
+
+impl Shared {
+    /// Creates the state of a handle for `recog` and registers it for [`detach`].
+    fn register(recog: *mut libjulius_sys::Recog) -> Arc<Self> {
+        let shared = Arc::new(Self::default());
+        shared.lock().recog = Some(RecogPtr(recog));
+        let mut handles = HANDLES.lock().unwrap_or_else(|e| e.into_inner());
+        handles.retain(|(_, shared)| shared.strong_count() > 0);
+        handles.push((recog as usize, Arc::downgrade(&shared)));
+        shared
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    /// Handle of a zeroed recognizer, which is only used as a key.
+    fn control() -> (Box<libjulius_sys::Recog>, RecogControl) {
+        let recog: Box<libjulius_sys::Recog> = Box::new(unsafe { std::mem::zeroed() });
+        let control = RecogControl(Shared::register(&*recog as *const _ as *mut _));
+        (recog, control)
+    }
+
+    #[test]
+    fn waiting_ends_when_recog_is_detached() {
+        for paused in [true, false] {
+            let (recog, control) = control();
+            assert!(!control.wait_paused_timeout(Duration::from_millis(10)));
+            // Waiting for a resume needs a paused recognizer
+            control.0.lock().paused = !paused;
+
+            let waiter = {
+                let control = control.clone();
+                thread::spawn(move || {
+                    if paused {
+                        control.wait_paused()
+                    } else {
+                        control.wait_resumed()
+                    }
+                })
+            };
+            thread::sleep(Duration::from_millis(50));
+            detach(&recog);
+            assert!(!waiter.join().unwrap());
+
+            // Requests are ignored and waiting fails right away afterwards
+            control.pause();
+            assert!(!control.is_paused());
+            assert!(!control.wait_paused());
+            assert!(!control.wait_resumed());
+        }
+    }
+}
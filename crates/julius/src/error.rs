@@ -0,0 +1,106 @@
+//! Errors carrying the messages libjulius printed while failing.
+
+use std::fmt;
+
+/// Number of trailing messages kept when no line looks like an error.
+const TRAILING_LINES: usize = 3;
+
+/// Error of a libjulius call, with the relevant lines of its jlog output.
+///
+/// Returned wrapped in [`anyhow::Error`]; use `downcast_ref` to inspect it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JuliusError {
+    message: String,
+    details: Vec<String>,
+    log: Vec<String>,
+}
+
+impl JuliusError {
+    pub(crate) fn new(message: impl Into<String>, log: Vec<String>) -> Self {
+        let mut details: Vec<String> = log
+            .iter()
+            .filter(|line| {
+                let line = line.to_ascii_lowercase();
+                line.contains("error") || line.contains("not found") || line.contains("failed")
+            })
+            .cloned()
+            .collect();
+        if details.is_empty() {
+            details = log[log.len().saturating_sub(TRAILING_LINES)..].to_vec();
+        }
+        Self {
+            message: message.into(),
+            details,
+            log,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+    /// Lines of the output explaining the error.
+    pub fn details(&self) -> &[String] {
+        &self.details
+    }
+    /// Whole output of libjulius during the failed call.
+    pub fn log(&self) -> &[String] {
+        &self.log
+    }
+}
+
+impl fmt::Display for JuliusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if !self.details.is_empty() {
+            write!(f, ": {}", self.details.join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for JuliusError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn picks_error_lines() {
+        let log = lines(
+            "STAT: include config: main.jconf\n\
+             Stat: init_phmm: Reading in HMM definition\n\
+             Error: init_phmm: failed to read am.htkdefs\n\
+             line 12: triphone a-k+i not found\n\
+             Stat: init_voca: read 10 words",
+        );
+        let error = JuliusError::new("Recog failed", log.clone());
+        assert_eq!(
+            error.details(),
+            [
+                "Error: init_phmm: failed to read am.htkdefs",
+                "line 12: triphone a-k+i not found"
+            ]
+        );
+        assert_eq!(error.log(), log);
+        assert_eq!(
+            error.to_string(),
+            "Recog failed: Error: init_phmm: failed to read am.htkdefs; \
+             line 12: triphone a-k+i not found"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_trailing_lines() {
+        let log = lines("Stat: a\nStat: b\nStat: c\nStat: d");
+        let error = JuliusError::new("Error in recognition", log);
+        assert_eq!(error.details(), ["Stat: b", "Stat: c", "Stat: d"]);
+
+        let error = JuliusError::new("JConf failed", Vec::new());
+        assert!(error.details().is_empty());
+        assert_eq!(error.to_string(), "JConf failed");
+    }
+}
@@ -0,0 +1,465 @@
+//! HTK-compatible feature extraction using libsent's front-end.
+
+use std::ptr::null_mut;
+
+use crate::{
+    param::{HtkParam, ParamKind},
+    Recog,
+};
+
+/// Weight of the initial cepstral mean, same as the default of `-cmnmapweight`.
+const CMN_MAP_WEIGHT: f32 = 100.0;
+
+/// Feature extraction parameters, equivalent to libsent's `Value`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MfccConfig {
+    /// Base parameter type, e.g. [`ParamKind::MFCC`].
+    pub basetype: u16,
+    pub smp_freq: i32,
+    /// Window length in samples.
+    pub framesize: i32,
+    /// Frame shift in samples.
+    pub frameshift: i32,
+    pub pre_emph: f32,
+    pub lifter: i32,
+    pub fbank_num: i32,
+    pub del_win: i32,
+    pub acc_win: i32,
+    pub sil_floor: f32,
+    pub escale: f32,
+    pub hipass: i32,
+    pub lopass: i32,
+    pub enormal: bool,
+    pub raw_e: bool,
+    pub zmeanframe: bool,
+    pub usepower: bool,
+    pub vtln_alpha: f32,
+    pub vtln_upper: f32,
+    pub vtln_lower: f32,
+    /// `_D`
+    pub delta: bool,
+    /// `_A`
+    pub acc: bool,
+    /// `_E`
+    pub energy: bool,
+    /// `_0`
+    pub c0: bool,
+    /// `_N`
+    pub absesup: bool,
+    /// `_Z`
+    pub cmn: bool,
+    pub cvn: bool,
+    pub mfcc_dim: i32,
+}
+
+impl Default for MfccConfig {
+    /// Julius' default parameters.
+    fn default() -> Self {
+        let mut value = unsafe { std::mem::zeroed::<libjulius_sys::Value>() };
+        unsafe { libjulius_sys::make_default_para(&mut value) };
+        Self::from_value(&value)
+    }
+}
+
+impl MfccConfig {
+    /// Returns the parameters of the first MFCC instance of `recog`.
+    ///
+    /// These include the parameters embedded in the acoustic model.
+    pub fn from_recog(recog: &Recog) -> Option<Self> {
+        let mfcc = unsafe { recog.0.mfcclist.as_ref()? };
+        let value = unsafe { mfcc.para.as_ref()? };
+        Some(Self::from_value(value))
+    }
+
+    pub(crate) fn from_value(value: &libjulius_sys::Value) -> Self {
+        Self {
+            basetype: value.basetype as u16,
+            smp_freq: value.smp_freq,
+            framesize: value.framesize,
+            frameshift: value.frameshift,
+            pre_emph: value.preEmph,
+            lifter: value.lifter,
+            fbank_num: value.fbank_num,
+            del_win: value.delWin,
+            acc_win: value.accWin,
+            sil_floor: value.silFloor,
+            escale: value.escale,
+            hipass: value.hipass,
+            lopass: value.lopass,
+            enormal: value.enormal != 0,
+            raw_e: value.raw_e != 0,
+            zmeanframe: value.zmeanframe != 0,
+            usepower: value.usepower != 0,
+            vtln_alpha: value.vtln_alpha,
+            vtln_upper: value.vtln_upper,
+            vtln_lower: value.vtln_lower,
+            delta: value.delta != 0,
+            acc: value.acc != 0,
+            energy: value.energy != 0,
+            c0: value.c0 != 0,
+            absesup: value.absesup != 0,
+            cmn: value.cmn != 0,
+            cvn: value.cvn != 0,
+            mfcc_dim: value.mfcc_dim,
+        }
+    }
+
+    pub(crate) fn to_value(&self) -> libjulius_sys::Value {
+        let baselen = self.mfcc_dim + self.c0 as i32 + self.energy as i32;
+        let vecbuflen = baselen * (1 + self.delta as i32 + self.acc as i32);
+        libjulius_sys::Value {
+            basetype: self.basetype as i16,
+            // -1 marks an unset sampling rate in libsent
+            smp_period: if self.smp_freq > 0 {
+                10_000_000 / self.smp_freq
+            } else {
+                -1
+            },
+            smp_freq: self.smp_freq,
+            framesize: self.framesize,
+            frameshift: self.frameshift,
+            preEmph: self.pre_emph,
+            lifter: self.lifter,
+            fbank_num: self.fbank_num,
+            delWin: self.del_win,
+            accWin: self.acc_win,
+            silFloor: self.sil_floor,
+            escale: self.escale,
+            hipass: self.hipass,
+            lopass: self.lopass,
+            enormal: self.enormal as i32,
+            raw_e: self.raw_e as i32,
+            zmeanframe: self.zmeanframe as i32,
+            usepower: self.usepower as i32,
+            vtln_alpha: self.vtln_alpha,
+            vtln_upper: self.vtln_upper,
+            vtln_lower: self.vtln_lower,
+            delta: self.delta as i32,
+            acc: self.acc as i32,
+            energy: self.energy as i32,
+            c0: self.c0 as i32,
+            absesup: self.absesup as i32,
+            cmn: self.cmn as i32,
+            cvn: self.cvn as i32,
+            mfcc_dim: self.mfcc_dim,
+            baselen,
+            vecbuflen,
+            veclen: vecbuflen - self.absesup as i32,
+            loaded: 1,
+        }
+    }
+
+    /// Sets the qualifiers and dimensions from a parameter kind and vector length.
+    pub fn apply_param_kind(&mut self, param_kind: ParamKind, veclen: usize) {
+        let mut value = self.to_value();
+        unsafe {
+            libjulius_sys::calc_para_from_header(&mut value, param_kind.0 as i16, veclen as i16)
+        };
+        *self = Self::from_value(&value);
+    }
+
+    /// Parameter kind of the extracted features.
+    pub fn param_kind(&self) -> ParamKind {
+        let mut qualifiers = 0;
+        for (enabled, qualifier) in [
+            (self.energy, ParamKind::ENERGY),
+            (self.absesup, ParamKind::ENERGY_SUP),
+            (self.delta, ParamKind::DELTA),
+            (self.acc, ParamKind::ACCL),
+            (self.cmn, ParamKind::CEPNORM),
+            (self.c0, ParamKind::ZEROTH),
+        ] {
+            if enabled {
+                qualifiers |= qualifier;
+            }
+        }
+        ParamKind::new(self.basetype, qualifiers)
+    }
+
+    /// Length of a feature vector.
+    pub fn veclen(&self) -> usize {
+        self.to_value().veclen as usize
+    }
+}
+
+/// Converts waveforms into feature vectors with libsent's `Wav2MFCC`.
+///
+/// The extractor can be moved to another thread, but not shared, as extracting updates its
+/// work areas.
+#[derive(Debug)]
+pub struct MfccExtractor {
+    config: MfccConfig,
+    value: libjulius_sys::Value,
+    work: *mut libjulius_sys::MFCCWork,
+    cmn: *mut libjulius_sys::CMNWork,
+}
+
+// The work areas are owned by the extractor, and libsent keeps no global state for them.
+unsafe impl Send for MfccExtractor {}
+
+impl MfccExtractor {
+    pub fn new(config: MfccConfig) -> Result<Self, anyhow::Error> {
+        if config.smp_freq <= 0 || config.framesize <= 0 || config.frameshift <= 0 {
+            return Err(anyhow::anyhow!("Invalid analysis parameters"));
+        }
+        let mut value = config.to_value();
+        let work = unsafe { libjulius_sys::WMP_work_new(&mut value) };
+        if work.is_null() {
+            return Err(anyhow::anyhow!("Failed to initialize MFCC computation"));
+        }
+        let cmn = if config.cmn || config.cvn {
+            unsafe { libjulius_sys::CMN_realtime_new(&mut value, CMN_MAP_WEIGHT, 0) }
+        } else {
+            null_mut()
+        };
+        Ok(Self {
+            config,
+            value,
+            work,
+            cmn,
+        })
+    }
+
+    pub fn config(&self) -> &MfccConfig {
+        &self.config
+    }
+
+    /// Computes the feature vectors of `samples`, one `Vec` per frame.
+    pub fn extract(&mut self, samples: &[i16]) -> Vec<Vec<f32>> {
+        let framesize = self.value.framesize as usize;
+        if samples.len() < framesize {
+            return Vec::new();
+        }
+        let frame_num = (samples.len() - framesize) / self.value.frameshift as usize + 1;
+        let vecbuflen = self.value.vecbuflen as usize;
+
+        let mut buffers = vec![vec![0.0f32; vecbuflen]; frame_num];
+        let mut ptrs: Vec<*mut f32> = buffers.iter_mut().map(|b| b.as_mut_ptr()).collect();
+        let mut wave = samples.to_vec();
+        unsafe {
+            libjulius_sys::Wav2MFCC(
+                wave.as_mut_ptr(),
+                ptrs.as_mut_ptr(),
+                &mut self.value,
+                wave.len() as i32,
+                self.work,
+                self.cmn,
+            );
+        }
+
+        if self.value.absesup != 0 {
+            // The absolute energy is the last element of the static part
+            let energy = self.value.baselen as usize - 1;
+            for buffer in &mut buffers {
+                buffer.remove(energy);
+            }
+        }
+        buffers
+    }
+
+    /// Computes the feature vectors of `samples` as an [`HtkParam`].
+    pub fn extract_param(&mut self, samples: &[i16]) -> Result<HtkParam, anyhow::Error> {
+        let sample_period = (self.value.frameshift * self.value.smp_period) as u32;
+        HtkParam::new(
+            self.config.param_kind(),
+            sample_period,
+            self.extract(samples),
+        )
+    }
+}
+
+impl Drop for MfccExtractor {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.cmn.is_null() {
+                libjulius_sys::CMN_realtime_free(self.cmn);
+            }
+            libjulius_sys::WMP_free(self.work);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    /// 12 MFCCs of 25ms frames every 10ms at 16kHz without qualifiers.
+    fn plain_config() -> MfccConfig {
+        MfccConfig {
+            basetype: ParamKind::MFCC,
+            smp_freq: 16000,
+            framesize: 400,
+            frameshift: 160,
+            pre_emph: 0.97,
+            lifter: 22,
+            fbank_num: 24,
+            del_win: 2,
+            acc_win: 2,
+            hipass: -1,
+            lopass: -1,
+            enormal: false,
+            raw_e: false,
+            zmeanframe: false,
+            usepower: false,
+            vtln_alpha: 1.0,
+            delta: false,
+            acc: false,
+            energy: false,
+            c0: false,
+            absesup: false,
+            cmn: false,
+            cvn: false,
+            mfcc_dim: 12,
+            ..MfccConfig::default()
+        }
+    }
+
+    fn waveform(len: usize) -> Vec<i16> {
+        let mut seed = 1u32;
+        (0..len)
+            .map(|i| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                let t = i as f64 / 16000.0;
+                let noise = ((seed >> 16) % 200) as f64 - 100.0;
+                (3000.0 * (2.0 * PI * 440.0 * t).sin()
+                    + 1500.0 * (2.0 * PI * 1800.0 * t).sin()
+                    + noise) as i16
+            })
+            .collect()
+    }
+
+    /// MFCCs of one frame following libsent's `WMP_calc`: pre-emphasis, Hamming window,
+    /// magnitude spectrum, mel filterbank, log, DCT and liftering.
+    fn reference_frame(frame: &[i16], config: &MfccConfig) -> Vec<f64> {
+        let n = frame.len();
+        let k = config.pre_emph as f64;
+        let mut wave: Vec<f64> = frame.iter().map(|&s| s as f64).collect();
+        for i in (1..n).rev() {
+            wave[i] -= wave[i - 1] * k;
+        }
+        wave[0] *= 1.0 - k;
+        for (i, w) in wave.iter_mut().enumerate() {
+            *w *= 0.54 - 0.46 * (2.0 * PI * i as f64 / (n - 1) as f64).cos();
+        }
+
+        let fft_n = n.next_power_of_two();
+        let fbank_num = config.fbank_num as usize;
+        let fres = 1.0e7 / (10_000_000.0 / config.smp_freq as f64 * fft_n as f64 * 700.0);
+        // Mel frequency of the 1-based FFT bin `k`
+        let mel = |k: usize| 1127.0 * (1.0 + (k - 1) as f64 * fres).ln();
+        let max_chan = fbank_num + 1;
+        let mhi = mel(fft_n / 2 + 1);
+        let cf: Vec<f64> = (0..=max_chan)
+            .map(|chan| chan as f64 / max_chan as f64 * mhi)
+            .collect();
+
+        let mut fbank = vec![0.0; fbank_num + 2];
+        for k in 2..=fft_n / 2 {
+            let (mut re, mut im) = (0.0, 0.0);
+            for (t, w) in wave.iter().enumerate() {
+                let angle = 2.0 * PI * ((k - 1) * t) as f64 / fft_n as f64;
+                re += w * angle.cos();
+                im -= w * angle.sin();
+            }
+            let magnitude = (re * re + im * im).sqrt();
+            let m = mel(k);
+            let chan = (1..=max_chan).find(|&c| cf[c] >= m).unwrap_or(max_chan + 1) - 1;
+            let weight = if chan > 0 {
+                (cf[chan + 1] - m) / (cf[chan + 1] - cf[chan])
+            } else {
+                (cf[1] - m) / cf[1]
+            };
+            if chan > 0 {
+                fbank[chan] += weight * magnitude;
+            }
+            if chan < fbank_num {
+                fbank[chan + 1] += magnitude - weight * magnitude;
+            }
+        }
+        let log_fbank: Vec<f64> = fbank[1..=fbank_num]
+            .iter()
+            .map(|&e| e.max(1.0).ln())
+            .collect();
+
+        let lifter = config.lifter as f64;
+        (1..=config.mfcc_dim as usize)
+            .map(|i| {
+                let c: f64 = log_fbank
+                    .iter()
+                    .enumerate()
+                    .map(|(k, e)| e * (PI * i as f64 / fbank_num as f64 * (k as f64 + 0.5)).cos())
+                    .sum();
+                let c = c * (2.0 / fbank_num as f64).sqrt();
+                c * (1.0 + lifter / 2.0 * (PI * i as f64 / lifter).sin())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_reference_mfcc() {
+        let config = plain_config();
+        let samples = waveform(400 + 160 * 4);
+        let mut extractor = MfccExtractor::new(config.clone()).unwrap();
+        let frames = extractor.extract(&samples);
+        assert_eq!(frames.len(), 5);
+
+        for (t, frame) in frames.iter().enumerate() {
+            let expected = reference_frame(&samples[t * 160..t * 160 + 400], &config);
+            assert_eq!(frame.len(), expected.len());
+            for (i, (&actual, expected)) in frame.iter().zip(expected).enumerate() {
+                assert!(
+                    (actual as f64 - expected).abs() < 0.01 + 0.001 * expected.abs(),
+                    "frame {} coefficient {}: {} != {}",
+                    t,
+                    i,
+                    actual,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn shapes_qualified_features() {
+        // MFCC_E_D_N: the absolute energy is dropped from 13 static and 13 delta elements
+        let config = MfccConfig {
+            energy: true,
+            delta: true,
+            absesup: true,
+            ..plain_config()
+        };
+        assert_eq!(config.veclen(), 25);
+        let kind = config.param_kind();
+        assert_eq!(kind.base(), ParamKind::MFCC);
+        assert_eq!(
+            kind.qualifiers(),
+            ParamKind::ENERGY | ParamKind::DELTA | ParamKind::ENERGY_SUP
+        );
+
+        let mut extractor = MfccExtractor::new(config).unwrap();
+        assert!(extractor.extract(&waveform(399)).is_empty());
+        let param = extractor.extract_param(&waveform(16000)).unwrap();
+        assert_eq!(param.frames.len(), 98);
+        assert!(param.frames.iter().all(|frame| frame.len() == 25));
+        assert_eq!(param.header.param_kind, kind);
+        // 10ms in 100ns units
+        assert_eq!(param.header.sample_period, 100_000);
+    }
+
+    #[test]
+    fn rejects_invalid_analysis_parameters() {
+        let config = MfccConfig {
+            frameshift: 0,
+            ..plain_config()
+        };
+        assert!(MfccExtractor::new(config).is_err());
+    }
+
+    #[test]
+    fn extractor_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<MfccExtractor>();
+    }
+}
@@ -0,0 +1,133 @@
+//! GMM-based input verification (`-gmm`, `-gmmreject`).
+
+use std::{ffi::CStr, path::PathBuf};
+
+use crate::{JConf, Recog};
+
+/// Configuration of GMM-based input rejection.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GmmConfig {
+    /// HTK definition file of the GMMs (`-gmm`).
+    pub path: PathBuf,
+    /// Number of Gaussians to compute per mixture (`-gmmnum`).
+    pub gprune_num: Option<u32>,
+    /// Names of the GMMs to be rejected as noise (`-gmmreject`).
+    pub reject: Vec<String>,
+}
+
+impl GmmConfig {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            ..Default::default()
+        }
+    }
+    pub fn with_gprune_num(mut self, num: u32) -> Self {
+        self.gprune_num = Some(num);
+        self
+    }
+    pub fn with_reject<I: IntoIterator<Item = S>, S: Into<String>>(mut self, names: I) -> Self {
+        self.reject = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Writes the options into `jconf`.
+    pub fn apply(&self, jconf: &mut JConf) -> Result<(), anyhow::Error> {
+        let path = self
+            .path
+            .to_str()
+            .ok_or_else(|| anyhow::anyhow!("Invalid GMM path: {}", self.path.display()))?;
+        if path.is_empty() || path.contains(char::is_whitespace) {
+            return Err(anyhow::anyhow!("Invalid GMM path: {}", path));
+        }
+        let mut options = format!("-gmm {}", path);
+        if let Some(num) = self.gprune_num {
+            options += &format!(" -gmmnum {}", num);
+        }
+        if !self.reject.is_empty() {
+            if let Some(name) = self
+                .reject
+                .iter()
+                .find(|n| n.is_empty() || n.contains(|c: char| c == ',' || c.is_whitespace()))
+            {
+                return Err(anyhow::anyhow!("Invalid GMM name: {:?}", name));
+            }
+            options += &format!(" -gmmreject {}", self.reject.join(","));
+        }
+        jconf.load_string(&options)
+    }
+}
+
+/// Score of a single GMM.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GmmScore {
+    pub name: String,
+    /// Accumulated log likelihood of the input.
+    pub score: f32,
+    /// Whether the model is accepted as voice, i.e. not listed in `-gmmreject`.
+    pub is_voice: bool,
+}
+
+/// Result of GMM computation, available from [`CallbackType::ResultGmm`](crate::CallbackType::ResultGmm).
+#[derive(Debug, Clone, PartialEq)]
+pub struct GmmResult {
+    /// Scores of every GMM in the definition order.
+    pub scores: Vec<GmmScore>,
+    /// Index of the best model in `scores`.
+    pub best: usize,
+    /// Confidence of the best model.
+    pub confidence: f32,
+    /// Number of frames the scores are accumulated over.
+    pub frame_count: i32,
+}
+
+impl GmmResult {
+    /// Copies the GMM result of the latest input, or `None` when GMM is not in use.
+    pub fn from_recog(recog: &Recog) -> Option<Self> {
+        let gmm = unsafe { recog.0.gmm.as_ref()? };
+        let gc = unsafe { recog.0.gc.as_ref()? };
+        if gc.max_d.is_null() || gc.gmm_score.is_null() {
+            return None;
+        }
+
+        let mut scores = Vec::new();
+        let mut best = None;
+        let mut current = gmm.start;
+        while let Some(d) = unsafe { current.as_ref() } {
+            let i = scores.len();
+            if current == gc.max_d {
+                best = Some(i);
+            }
+            let name = if d.name.is_null() {
+                String::new()
+            } else {
+                unsafe { CStr::from_ptr(d.name) }
+                    .to_string_lossy()
+                    .into_owned()
+            };
+            scores.push(GmmScore {
+                name,
+                score: unsafe { *gc.gmm_score.add(i) },
+                is_voice: gc.is_voice.is_null() || unsafe { *gc.is_voice.add(i) } != 0,
+            });
+            current = d.next;
+        }
+
+        Some(Self {
+            scores,
+            best: best?,
+            confidence: gc.gmm_max_cm,
+            frame_count: gc.framecount,
+        })
+    }
+
+    /// The model with the highest score.
+    pub fn best(&self) -> &GmmScore {
+        &self.scores[self.best]
+    }
+
+    /// Whether the input is accepted as voice.
+    pub fn is_voice(&self) -> bool {
+        self.best().is_voice
+    }
+}
@@ -0,0 +1,303 @@
+//! Speech input handled by libjulius itself, including the microphone.
+//!
+//! Microphone input needs the `alsa` or `pulseaudio` feature, which builds the corresponding
+//! backend of libjulius; with both, each source selects its backend. Devices can be listed only
+//! with ALSA (`list_microphones`): the PulseAudio backend of libjulius always records from the
+//! default source, so there is no device to choose.
+
+#[cfg(any(feature = "alsa", feature = "pulseaudio"))]
+use std::fmt;
+use std::path::PathBuf;
+
+#[cfg(any(feature = "alsa", feature = "pulseaudio"))]
+use crate::error::JuliusError;
+use crate::{JConf, Recog};
+
+/// Speech input of libjulius, equivalent to `-input`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputSource {
+    /// Audio file, `-input rawfile`.
+    File(PathBuf),
+    /// Audio from the standard input, `-input stdin`.
+    Stdin,
+    /// Microphone through ALSA, `-input alsa`.
+    ///
+    /// `device` is an ALSA PCM name such as `hw:1,0`, see `list_microphones`. `None` uses
+    /// `ALSADEV` or `default`.
+    #[cfg(feature = "alsa")]
+    Alsa { device: Option<String> },
+    /// Microphone through PulseAudio, `-input pulseaudio`, which always records from the
+    /// default source.
+    #[cfg(feature = "pulseaudio")]
+    PulseAudio,
+}
+
+impl InputSource {
+    /// Argument of `-input`.
+    fn input_option(&self) -> &'static str {
+        match self {
+            Self::File(_) => "rawfile",
+            Self::Stdin => "stdin",
+            #[cfg(feature = "alsa")]
+            Self::Alsa { .. } => "alsa",
+            #[cfg(feature = "pulseaudio")]
+            Self::PulseAudio => "pulseaudio",
+        }
+    }
+}
+
+impl<'a> JConf<'a> {
+    /// Selects the speech input. This must be done before creating [`Recog`].
+    ///
+    /// The option parser of libjulius sets the input, so the related settings such as the
+    /// real-time processing of microphone input follow as with `-input`.
+    pub fn set_input(&mut self, source: &InputSource) -> Result<(), anyhow::Error> {
+        self.load_string(&format!("-input {}", source.input_option()))
+    }
+}
+
+impl<'a> Recog<'a> {
+    /// Opens `source`, which must be the input selected by [`JConf::set_input`].
+    ///
+    /// Failures to open a microphone are returned as [`MicError`].
+    pub fn open_input(&mut self, source: &InputSource) -> Result<(), anyhow::Error> {
+        match source {
+            InputSource::File(path) => {
+                let path = path
+                    .to_str()
+                    .ok_or_else(|| anyhow::anyhow!("Path is not valid UTF-8"))?;
+                self.open_stream(Some(path))
+            }
+            InputSource::Stdin => self.open_stream(None),
+            #[cfg(feature = "alsa")]
+            InputSource::Alsa { device } => self.open_microphone(device.as_deref()),
+            #[cfg(feature = "pulseaudio")]
+            InputSource::PulseAudio => self.open_microphone(None),
+        }
+    }
+
+    #[cfg(any(feature = "alsa", feature = "pulseaudio"))]
+    fn open_microphone(&mut self, device: Option<&str>) -> Result<(), anyhow::Error> {
+        self.open_stream(device)
+            .map_err(|e| match e.downcast::<JuliusError>() {
+                Ok(error) => MicError::new(device.map(str::to_string), error).into(),
+                Err(e) => e,
+            })
+    }
+}
+
+/// Kind of [`MicError`], guessed from the messages of the backend.
+#[cfg(any(feature = "alsa", feature = "pulseaudio"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MicErrorKind {
+    /// The device does not exist.
+    NotFound,
+    /// The device is used by another process.
+    Busy,
+    /// Access to the device is denied.
+    PermissionDenied,
+    /// The sound server is not running.
+    ConnectionRefused,
+    Other,
+}
+
+/// Failure to open a microphone.
+#[cfg(any(feature = "alsa", feature = "pulseaudio"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MicError {
+    kind: MicErrorKind,
+    device: Option<String>,
+    error: JuliusError,
+}
+
+#[cfg(any(feature = "alsa", feature = "pulseaudio"))]
+impl MicError {
+    fn new(device: Option<String>, error: JuliusError) -> Self {
+        let log = error.log().join("\n").to_ascii_lowercase();
+        let kind = if log.contains("busy") {
+            MicErrorKind::Busy
+        } else if log.contains("permission denied") {
+            MicErrorKind::PermissionDenied
+        } else if log.contains("connection refused") {
+            MicErrorKind::ConnectionRefused
+        } else if log.contains("no such") || log.contains("not found") {
+            MicErrorKind::NotFound
+        } else {
+            MicErrorKind::Other
+        };
+        Self {
+            kind,
+            device,
+            error,
+        }
+    }
+
+    pub fn kind(&self) -> MicErrorKind {
+        self.kind
+    }
+    /// Device given in `InputSource::Alsa`.
+    pub fn device(&self) -> Option<&str> {
+        self.device.as_deref()
+    }
+    pub fn julius_error(&self) -> &JuliusError {
+        &self.error
+    }
+}
+
+#[cfg(any(feature = "alsa", feature = "pulseaudio"))]
+impl fmt::Display for MicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let device = self.device.as_deref().unwrap_or("default");
+        match self.kind {
+            MicErrorKind::NotFound => write!(f, "Microphone {} not found", device)?,
+            MicErrorKind::Busy => write!(f, "Microphone {} is busy", device)?,
+            MicErrorKind::PermissionDenied => {
+                write!(f, "Permission denied for microphone {}", device)?
+            }
+            MicErrorKind::ConnectionRefused => write!(f, "Sound server is not running")?,
+            MicErrorKind::Other => write!(f, "Failed to open microphone {}", device)?,
+        }
+        write!(f, " ({})", self.error)
+    }
+}
+
+#[cfg(any(feature = "alsa", feature = "pulseaudio"))]
+impl std::error::Error for MicError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(feature = "alsa")]
+pub use alsa::{list_microphones, MicDevice};
+
+#[cfg(feature = "alsa")]
+mod alsa {
+    use std::{
+        ffi::{c_char, c_int, c_void, CStr},
+        ptr::null_mut,
+    };
+
+    extern "C" {
+        fn snd_device_name_hint(
+            card: c_int,
+            iface: *const c_char,
+            hints: *mut *mut *mut c_void,
+        ) -> c_int;
+        fn snd_device_name_get_hint(hint: *const c_void, id: *const c_char) -> *mut c_char;
+        fn snd_device_name_free_hint(hints: *mut *mut c_void) -> c_int;
+        fn snd_strerror(errnum: c_int) -> *const c_char;
+    }
+
+    /// ALSA PCM device which can capture.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct MicDevice {
+        /// Name for [`InputSource::Alsa`](super::InputSource::Alsa).
+        pub name: String,
+        pub description: Option<String>,
+    }
+
+    /// Lists the ALSA PCM devices which can capture.
+    pub fn list_microphones() -> Result<Vec<MicDevice>, anyhow::Error> {
+        let mut hints = null_mut();
+        let err = unsafe { snd_device_name_hint(-1, c"pcm".as_ptr(), &mut hints) };
+        if err < 0 {
+            let message = unsafe { CStr::from_ptr(snd_strerror(err)) };
+            return Err(anyhow::anyhow!(
+                "Failed to list ALSA devices: {}",
+                message.to_string_lossy()
+            ));
+        }
+
+        let mut devices = vec![];
+        let mut hint = hints;
+        while !unsafe { *hint }.is_null() {
+            let name = get_hint(unsafe { *hint }, c"NAME");
+            let direction = get_hint(unsafe { *hint }, c"IOID");
+            // No IOID means both directions
+            if let (Some(name), None | Some("Input")) = (name, direction.as_deref()) {
+                devices.push(MicDevice {
+                    name,
+                    description: get_hint(unsafe { *hint }, c"DESC"),
+                });
+            }
+            hint = unsafe { hint.add(1) };
+        }
+        unsafe { snd_device_name_free_hint(hints) };
+        Ok(devices)
+    }
+
+    fn get_hint(hint: *const c_void, id: &CStr) -> Option<String> {
+        let value = unsafe { snd_device_name_get_hint(hint, id.as_ptr()) };
+        if value.is_null() {
+            return None;
+        }
+        let string = unsafe { CStr::from_ptr(value) }
+            .to_string_lossy()
+            .into_owned();
+        unsafe { libc::free(value as *mut c_void) };
+        Some(string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selects_input_option() {
+        assert_eq!(InputSource::File("a.wav".into()).input_option(), "rawfile");
+        assert_eq!(InputSource::Stdin.input_option(), "stdin");
+        #[cfg(feature = "alsa")]
+        assert_eq!(InputSource::Alsa { device: None }.input_option(), "alsa");
+        #[cfg(feature = "pulseaudio")]
+        assert_eq!(InputSource::PulseAudio.input_option(), "pulseaudio");
+    }
+
+    #[cfg(any(feature = "alsa", feature = "pulseaudio"))]
+    #[test]
+    fn classifies_mic_errors() {
+        let error = |line: &str| {
+            let log = vec![
+                "STAT: ###### initialize input device".to_string(),
+                line.to_string(),
+            ];
+            MicError::new(
+                Some("hw:1,0".to_string()),
+                JuliusError::new("Failed to open input stream", log),
+            )
+        };
+        let cases = [
+            (
+                "Error: adin_alsa_begin: cannot open PCM device hw:1,0 (Device or resource busy)",
+                MicErrorKind::Busy,
+            ),
+            (
+                "Error: adin_alsa_begin: cannot open PCM device hw:1,0 (Permission denied)",
+                MicErrorKind::PermissionDenied,
+            ),
+            (
+                "Error: adin_pulseaudio: pa_simple_new() failed: Connection refused",
+                MicErrorKind::ConnectionRefused,
+            ),
+            (
+                "Error: adin_alsa_begin: cannot open PCM device hw:1,0 (No such file or directory)",
+                MicErrorKind::NotFound,
+            ),
+            (
+                "Error: adin_alsa_begin: unknown failure",
+                MicErrorKind::Other,
+            ),
+        ];
+        for (line, kind) in cases {
+            let error = error(line);
+            assert_eq!(error.kind(), kind, "{}", line);
+            assert_eq!(error.device(), Some("hw:1,0"));
+            assert_eq!(error.julius_error().log()[1], line);
+        }
+        assert_eq!(
+            error("Error: busy").to_string(),
+            "Microphone hw:1,0 is busy (Failed to open input stream: Error: busy)"
+        );
+    }
+}
@@ -0,0 +1,281 @@
+//! Control of libjulius' message output (`jlog`).
+//!
+//! By default libjulius prints everything to stdout. With the `log` feature,
+//! [`forward_to_log`] sends each line to the [`log`] crate instead.
+
+use std::{
+    io::Read,
+    os::fd::FromRawFd,
+    sync::{Mutex, MutexGuard},
+};
+
+/// Outputs of libjulius, as addresses since raw pointers are not `Send`.
+struct Output {
+    /// Output outside captures, valid while `captures` is not empty.
+    base: usize,
+    /// Streams of the active captures. The last one receives the messages.
+    captures: Vec<usize>,
+}
+
+/// Serializes changes of the jlog output. Held only while the output is switched, never
+/// while libjulius runs.
+static OUTPUT: Mutex<Output> = Mutex::new(Output {
+    base: 0,
+    captures: Vec::new(),
+});
+
+fn lock_output() -> MutexGuard<'static, Output> {
+    OUTPUT.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Sets the output outside captures, taking effect when the active captures end.
+fn set_output(fp: *mut libc::FILE) {
+    let mut output = lock_output();
+    if output.captures.is_empty() {
+        unsafe { libjulius_sys::jlog_set_output(fp as *mut libjulius_sys::FILE) };
+    } else {
+        output.base = fp as usize;
+    }
+}
+
+/// Makes `fp` the output until [`end_capture`], and returns the output outside captures.
+fn begin_capture(fp: *mut libc::FILE) -> *mut libc::FILE {
+    let mut output = lock_output();
+    if output.captures.is_empty() {
+        output.base = unsafe { libjulius_sys::jlog_get_fp() } as usize;
+    }
+    output.captures.push(fp as usize);
+    unsafe { libjulius_sys::jlog_set_output(fp as *mut libjulius_sys::FILE) };
+    output.base as *mut libc::FILE
+}
+
+/// Switches the output from `fp` back to the capture started before, or to the output outside
+/// captures, and writes `pending` there. `fp` is no longer used by libjulius afterwards.
+fn end_capture(fp: *mut libc::FILE, pending: &[u8]) {
+    let mut output = lock_output();
+    output.captures.retain(|&capture| capture != fp as usize);
+    let current = output.captures.last().copied().unwrap_or(output.base) as *mut libc::FILE;
+    unsafe {
+        libjulius_sys::jlog_set_output(current as *mut libjulius_sys::FILE);
+        if !current.is_null() && !pending.is_empty() {
+            libc::fwrite(pending.as_ptr() as *const _, 1, pending.len(), current);
+            libc::fflush(current);
+        }
+    }
+}
+
+/// Enables or disables verbose messages such as the startup banner and the model summary.
+pub fn set_verbose(enabled: bool) {
+    unsafe {
+        if enabled {
+            libjulius_sys::j_enable_verbose_message();
+        } else {
+            libjulius_sys::j_disable_verbose_message();
+        }
+    }
+}
+
+/// Enables or disables debug messages.
+pub fn set_debug(enabled: bool) {
+    unsafe {
+        if enabled {
+            libjulius_sys::j_enable_debug_message();
+        } else {
+            libjulius_sys::j_disable_debug_message();
+        }
+    }
+}
+
+/// Discards every message of libjulius.
+pub fn disable_output() {
+    set_output(std::ptr::null_mut());
+}
+
+/// Runs `f` while writing the messages of libjulius into a temporary file, and returns its lines.
+///
+/// The messages are also written to the previous output afterwards, so nothing is lost.
+/// Messages of libjulius running on other threads meanwhile are captured as well.
+pub(crate) fn capture<T>(f: impl FnOnce() -> T) -> Result<(T, Vec<String>), anyhow::Error> {
+    let tmp = unsafe { libc::tmpfile() };
+    if tmp.is_null() {
+        return Err(anyhow::anyhow!(
+            "Failed to create a temporary file for the messages: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    let fd = unsafe { libc::dup(libc::fileno(tmp)) };
+    if fd == -1 {
+        let error = std::io::Error::last_os_error();
+        unsafe { libc::fclose(tmp) };
+        return Err(anyhow::anyhow!(
+            "Failed to duplicate the temporary file for the messages: {}",
+            error
+        ));
+    }
+    let mut file = unsafe { std::fs::File::from_raw_fd(fd) };
+
+    begin_capture(tmp);
+    let result = f();
+    let mut content = Vec::new();
+    unsafe {
+        libc::fflush(tmp);
+        libc::rewind(tmp);
+    }
+    let _ = file.read_to_end(&mut content);
+    end_capture(tmp, &content);
+    unsafe { libc::fclose(tmp) };
+
+    let lines = String::from_utf8_lossy(&content)
+        .lines()
+        .map(|line| line.trim_end().to_string())
+        .filter(|line| !line.is_empty())
+        .collect();
+    Ok((result, lines))
+}
+
+/// Number of trailing lines kept by [`capture_streaming`].
+const STREAMING_LINES: usize = 200;
+
+/// Like [`capture`] for long calls such as recognition: the messages are passed on to the
+/// output outside captures as they are written, and only the last lines are returned.
+///
+/// The jlog output is locked only while it is switched, so other decoders and captures from
+/// callbacks run meanwhile.
+pub(crate) fn capture_streaming<T>(f: impl FnOnce() -> T) -> (T, Vec<String>) {
+    use std::{
+        collections::VecDeque,
+        fs::File,
+        io::{BufRead, BufReader},
+    };
+
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return (f(), Vec::new());
+    }
+    let [read_fd, write_fd] = fds;
+    let fp = unsafe { libc::fdopen(write_fd, c"w".as_ptr()) };
+    if fp.is_null() {
+        unsafe {
+            libc::close(read_fd);
+            libc::close(write_fd);
+        }
+        return (f(), Vec::new());
+    }
+    unsafe { libc::setvbuf(fp, std::ptr::null_mut(), libc::_IOLBF, 0) };
+
+    let base = begin_capture(fp);
+    // The output outside captures is stdout, null or the never closed pipe of
+    // `forward_to_log`, so it stays valid; stdio streams lock themselves
+    let base_addr = base as usize;
+    let reader = BufReader::new(unsafe { File::from_raw_fd(read_fd) });
+    let tee = std::thread::spawn(move || {
+        let base = base_addr as *mut libc::FILE;
+        let mut lines = VecDeque::new();
+        for line in reader.split(b'\n') {
+            let Ok(mut line) = line else { break };
+            if !base.is_null() {
+                line.push(b'\n');
+                unsafe {
+                    libc::fwrite(line.as_ptr() as *const _, 1, line.len(), base);
+                    libc::fflush(base);
+                }
+            }
+            let line = String::from_utf8_lossy(&line).trim_end().to_string();
+            if line.is_empty() {
+                continue;
+            }
+            if lines.len() == STREAMING_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line);
+        }
+        Vec::from(lines)
+    });
+
+    let result = f();
+    unsafe { libc::fflush(fp) };
+    end_capture(fp, &[]);
+    unsafe { libc::fclose(fp) };
+    (result, tee.join().unwrap_or_default())
+}
+
+/// Writes an error from the Rust side to the output of libjulius, next to its own messages.
+#[cfg(any(feature = "wav", feature = "flac"))]
+pub(crate) fn error(message: impl std::fmt::Display) {
+    let fp = unsafe { libjulius_sys::jlog_get_fp() } as *mut libc::FILE;
+    if fp.is_null() {
+        return;
+    }
+    let line = format!("Error: {}\n", message);
+    unsafe {
+        libc::fwrite(line.as_ptr() as *const _, 1, line.len(), fp);
+        libc::fflush(fp);
+    }
+}
+
+/// Level of a line written by libjulius, judged from its prefix.
+#[cfg(feature = "log")]
+fn line_level(line: &str) -> log::Level {
+    let prefix = line.trim_start().to_ascii_lowercase();
+    if prefix.starts_with("error") {
+        log::Level::Error
+    } else if prefix.starts_with("warning") {
+        log::Level::Warn
+    } else if prefix.starts_with("stat:") {
+        log::Level::Debug
+    } else {
+        log::Level::Info
+    }
+}
+
+/// Redirects the messages of libjulius to the [`log`] crate with the target `libjulius`.
+///
+/// Messages are written into a pipe and read line by line on a background thread.
+/// Calling this again has no effect.
+#[cfg(feature = "log")]
+pub fn forward_to_log() -> Result<(), anyhow::Error> {
+    use std::{
+        fs::File,
+        io::{BufRead, BufReader},
+    };
+
+    static FORWARDING: Mutex<bool> = Mutex::new(false);
+    let mut forwarding = FORWARDING.lock().unwrap_or_else(|e| e.into_inner());
+    if *forwarding {
+        return Ok(());
+    }
+
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let [read_fd, write_fd] = fds;
+    let fp = unsafe { libc::fdopen(write_fd, c"w".as_ptr()) };
+    if fp.is_null() {
+        let error = std::io::Error::last_os_error();
+        unsafe {
+            libc::close(read_fd);
+            libc::close(write_fd);
+        }
+        return Err(error.into());
+    }
+    unsafe { libc::setvbuf(fp, std::ptr::null_mut(), libc::_IOLBF, 0) };
+
+    let reader = BufReader::new(unsafe { File::from_raw_fd(read_fd) });
+    std::thread::Builder::new()
+        .name("julius-jlog".to_string())
+        .spawn(move || {
+            for line in reader.split(b'\n') {
+                let Ok(line) = line else { break };
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim_end();
+                if !line.is_empty() {
+                    log::log!(target: "libjulius", line_level(line), "{}", line);
+                }
+            }
+        })?;
+
+    set_output(fp);
+    *forwarding = true;
+    Ok(())
+}
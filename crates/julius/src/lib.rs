@@ -3,16 +3,36 @@ use std::{
     ptr::null_mut,
 };
 
-use adin::ADIn;
+use adin::{ADIn, AdinRead, AudioSource, InputFormat, Resampler, Sample, SampleConverter};
+use error::JuliusError;
 use iter::BindIterator;
-use recog_process::RecogProcess;
+use param::HtkParam;
+use recog_process::{PartialResult, RecogProcess};
+use segment::{Segment, SegmentTracker};
 use strum_macros::FromRepr;
 
 mod iter;
 
 pub mod adin;
+pub mod adinnet;
+#[cfg(any(feature = "wav", feature = "flac"))]
+pub mod audio_file;
+pub mod confnet;
+pub mod control;
+pub mod error;
+pub mod features;
+pub mod gmm;
+pub mod input;
+pub mod jlog;
+pub mod nbest;
+pub mod param;
 pub mod recog_process;
+pub mod segment;
 pub mod sentence_align;
+pub mod word_graph;
+#[cfg(feature = "tokio")]
+pub mod stream;
+pub mod vad;
 
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, FromRepr)]
@@ -70,14 +90,41 @@ impl<'a> JConf<'a> {
     }
     pub fn from_string(string: &str) -> Result<Self, anyhow::Error> {
         let cstr = CString::new(string)?;
-        let jconf = unsafe { libjulius_sys::j_config_load_string_new(cstr.into_raw()) };
+        let (jconf, log) =
+            jlog::capture(|| unsafe { libjulius_sys::j_config_load_string_new(cstr.into_raw()) })?;
         if jconf.is_null() {
-            Err(anyhow::anyhow!("JConf failed"))
+            Err(JuliusError::new("JConf failed", log).into())
         } else {
             Ok(Self(unsafe { &mut *jconf }))
         }
     }
 
+    /// Applies options written in the jconf format on top of the current configuration.
+    pub fn load_string(&mut self, string: &str) -> Result<(), anyhow::Error> {
+        let cstr = CString::new(string)?.into_raw();
+        let captured =
+            jlog::capture(|| unsafe { libjulius_sys::j_config_load_string(self.0, cstr) });
+        drop(unsafe { CString::from_raw(cstr) });
+        let (ret, log) = captured?;
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(JuliusError::new(format!("Failed to load options: {}", string), log).into())
+        }
+    }
+
+    /// Sets the number of sentence hypotheses to find (`-n`) and output (`-output`)
+    /// in every search configuration.
+    pub fn set_nbest(&mut self, n: usize) {
+        let n = n.max(1) as i32;
+        let mut search = self.0.search_root;
+        while let Some(s) = unsafe { search.as_mut() } {
+            s.pass2.nbest = n;
+            s.output.output_hypo_maxnum = n;
+            search = s.next;
+        }
+    }
+
     pub unsafe fn as_raw_ptr(&self) -> *mut libjulius_sys::Jconf {
         self.0 as *const libjulius_sys::Jconf as *mut libjulius_sys::Jconf
     }
@@ -95,10 +142,12 @@ impl<'a> Drop for JConf<'a> {
 pub struct Recog<'a>(&'a mut libjulius_sys::Recog);
 impl<'a> Recog<'a> {
     pub fn from_jconf(jconf: JConf) -> Result<Self, anyhow::Error> {
-        let recog = unsafe { libjulius_sys::j_create_instance_from_jconf(&mut *jconf.0) };
+        let (recog, log) = jlog::capture(|| unsafe {
+            libjulius_sys::j_create_instance_from_jconf(&mut *jconf.0)
+        })?;
         std::mem::forget(jconf);
         if recog.is_null() {
-            Err(anyhow::anyhow!("Recog failed"))
+            Err(JuliusError::new("Recog failed", log).into())
         } else {
             Ok(Self(unsafe { &mut *recog }))
         }
@@ -114,14 +163,17 @@ impl<'a> Recog<'a> {
     }
 
     pub fn adin_init(&mut self) -> Result<(), anyhow::Error> {
-        let ret = unsafe { libjulius_sys::j_adin_init(&mut *self.0) };
+        let (ret, log) = jlog::capture(|| unsafe { libjulius_sys::j_adin_init(&mut *self.0) })?;
         match ret {
             1 => Ok(()),
-            0 => Err(anyhow::anyhow!("Failed to initialize input device")),
+            0 => Err(JuliusError::new("Failed to initialize input device", log).into()),
             _ => unreachable!(),
         }
     }
 
+    pub fn adin(&self) -> &ADIn {
+        unsafe { &*(self.0.adin as *const ADIn) }
+    }
     pub(crate) fn get_adin_mut(&mut self) -> &mut ADIn {
         unsafe { &mut *(self.0.adin as *mut ADIn) }
     }
@@ -140,9 +192,168 @@ impl<'a> Recog<'a> {
     /// - enable_thread
     /// - down_sample
     ///
-    pub fn custom_adin<T: FnMut(usize) -> Option<U> + 'a, U: AsRef<[i16]>>(&mut self, ad_read: T) {
+    pub fn custom_adin<T: FnMut(usize) -> Option<U> + 'a, U: AsRef<[i16]>>(
+        &mut self,
+        mut ad_read: T,
+    ) {
+        self.custom_adin_segmented(move |max| ad_read(max).into());
+    }
+
+    /// Setup custom ADIn which can also end segments or report errors.
+    ///
+    /// See [`Recog::custom_adin`] for the members of ADIn that interfere with custom ADIn.
+    pub fn custom_adin_segmented<T: FnMut(usize) -> AdinRead<U> + 'a, U: AsRef<[i16]>>(
+        &mut self,
+        mut ad_read: T,
+    ) {
         self.get_adin_mut().ad_read_inject_prepare();
-        self.add_callback_adin_inject(ad_read);
+        let mut segment_ended = false;
+        self.add_callback_adin_inject(move |adin: &mut ADIn, max| {
+            let data = ad_read(max);
+            match &data {
+                AdinRead::Samples(samples) if segment_ended && !samples.as_ref().is_empty() => {
+                    adin.start_injected_segment();
+                    segment_ended = false;
+                }
+                AdinRead::EndOfSegment => segment_ended = true,
+                _ => {}
+            }
+            data
+        });
+    }
+
+    /// Setup custom ADIn with samples of any [`Sample`] type.
+    ///
+    /// `ad_read` receives the maximum number of interleaved samples to return.
+    /// The samples are down-mixed or channel-selected according to `format` and scaled into `SP16`.
+    ///
+    /// See [`Recog::custom_adin`] for the members of ADIn that interfere with custom ADIn.
+    pub fn custom_adin_with_format<S, T, U>(
+        &mut self,
+        format: InputFormat,
+        ad_read: T,
+    ) -> Result<(), anyhow::Error>
+    where
+        S: Sample + 'a,
+        T: FnMut(usize) -> Option<U> + 'a,
+        U: AsRef<[S]>,
+    {
+        let sample_rate = self.sampling_rate();
+        self.custom_adin_with_rate(format, sample_rate, ad_read)
+    }
+
+    /// Setup custom ADIn with samples recorded at `sample_rate`.
+    ///
+    /// In addition to [`Recog::custom_adin_with_format`], the samples are resampled to
+    /// [`Recog::sampling_rate`] before injection.
+    pub fn custom_adin_with_rate<S, T, U>(
+        &mut self,
+        format: InputFormat,
+        sample_rate: u32,
+        mut ad_read: T,
+    ) -> Result<(), anyhow::Error>
+    where
+        S: Sample + 'a,
+        T: FnMut(usize) -> Option<U> + 'a,
+        U: AsRef<[S]>,
+    {
+        self.custom_adin_converted(format, sample_rate, move |max| ad_read(max).into())
+    }
+
+    /// Setup custom ADIn reading from an [`AudioSource`].
+    ///
+    /// The samples are converted and resampled as in [`Recog::custom_adin_with_rate`].
+    pub fn custom_adin_source<A: AudioSource + 'a>(
+        &mut self,
+        mut source: A,
+    ) -> Result<(), anyhow::Error> {
+        let format = source.format();
+        let sample_rate = source.sample_rate();
+        self.custom_adin_converted(format, sample_rate, move |max| source.read(max))
+    }
+
+    fn custom_adin_converted<S, T, U>(
+        &mut self,
+        format: InputFormat,
+        sample_rate: u32,
+        mut ad_read: T,
+    ) -> Result<(), anyhow::Error>
+    where
+        S: Sample + 'a,
+        T: FnMut(usize) -> AdinRead<U> + 'a,
+        U: AsRef<[S]>,
+    {
+        let mut converter = SampleConverter::<S>::new(format)?;
+        let mut resampler = Resampler::new(sample_rate, self.sampling_rate())?;
+        let mut converted: Vec<i16> = Vec::new();
+        let mut pending: Vec<i16> = Vec::new();
+        // Delivered after the samples flushed before it
+        let mut marker: Option<AdinRead<Vec<i16>>> = None;
+        self.custom_adin_segmented(move |max| {
+            if pending.is_empty() {
+                if let Some(marker) = marker.take() {
+                    return marker;
+                }
+                match ad_read(max * format.channels) {
+                    AdinRead::Samples(data) => {
+                        converted.clear();
+                        converter.convert(data.as_ref(), &mut converted);
+                        resampler.process(&converted, &mut pending);
+                    }
+                    AdinRead::EndOfSegment => {
+                        resampler.flush(&mut pending);
+                        marker = Some(AdinRead::EndOfSegment);
+                    }
+                    AdinRead::EndOfStream => {
+                        resampler.flush(&mut pending);
+                        marker = Some(AdinRead::EndOfStream);
+                    }
+                    AdinRead::Error => return AdinRead::Error,
+                }
+                // Nothing to inject yet, libjulius calls again
+                if pending.is_empty() {
+                    if let Some(marker) = marker.take() {
+                        return marker;
+                    }
+                }
+            }
+            let len = max.min(pending.len());
+            AdinRead::Samples(pending.drain(..len).collect())
+        });
+        Ok(())
+    }
+
+    /// Sampling rate of the input expected by the acoustic model.
+    pub fn sampling_rate(&self) -> u32 {
+        unsafe { (*self.0.jconf).input.sfreq as u32 }
+    }
+    /// Length of a frame shift in seconds.
+    pub fn frame_period(&self) -> f64 {
+        let input = unsafe { &(*self.0.jconf).input };
+        input.frameshift as f64 / input.sfreq as f64
+    }
+    /// Length of a frame shift in samples.
+    pub fn frameshift(&self) -> u32 {
+        unsafe { (*self.0.jconf).input.frameshift as u32 }
+    }
+
+    /// Changes the gain applied to the input samples.
+    pub fn set_input_scaling_factor(&mut self, factor: f32) {
+        unsafe { libjulius_sys::j_adin_change_input_scaling_factor(&mut *self.0, factor) }
+    }
+    /// Changes the level threshold of the built-in detection (`-lv`).
+    pub fn set_level_threshold(&mut self, thres: i32) {
+        self.get_adin_mut().set_level_threshold(thres);
+    }
+    /// Changes the number of zero crosses needed to trigger, see [`ADIn::noise_zerocross`].
+    pub fn set_noise_zerocross(&mut self, num: i32) {
+        self.get_adin_mut().set_noise_zerocross(num);
+    }
+    /// Turns the built-in level and zero-cross detection on or off, also for the custom ADIn path.
+    ///
+    /// Call after [`Recog::adin_init`], which resets it from the configuration.
+    pub fn set_silence_cut(&mut self, on: bool) {
+        self.get_adin_mut().set_silence_cut(on);
     }
 
     pub fn open_stream(&mut self, file_or_dev_name: Option<&str>) -> Result<(), anyhow::Error> {
@@ -150,7 +361,7 @@ impl<'a> Recog<'a> {
             Some(fv) => Some(CString::new(fv)?),
             None => None,
         };
-        let ret = unsafe {
+        let (ret, log) = jlog::capture(|| unsafe {
             libjulius_sys::j_open_stream(
                 &mut *self.0,
                 match cstr {
@@ -158,28 +369,64 @@ impl<'a> Recog<'a> {
                     None => null_mut(),
                 },
             )
-        };
+        })?;
         match ret {
             0 => Ok(()),
-            -1 => Err(anyhow::anyhow!("Error in input stream")),
-            -2 => Err(anyhow::anyhow!("Failed to begin input stream")),
+            -1 => Err(JuliusError::new("Error in input stream", log).into()),
+            -2 => Err(JuliusError::new("Failed to begin input stream", log).into()),
             _ => unreachable!(),
         }
     }
 
+    /// Opens a feature vector sequence as the input stream.
+    ///
+    /// Requires `-input htkparam` in the configuration and a single MFCC instance, since the
+    /// parameters are given to the first one like libjulius does with parameter files.
+    pub fn open_param(&mut self, param: &HtkParam) -> Result<(), anyhow::Error> {
+        self.check_param_input()?;
+        unsafe {
+            let mfcc = &mut *self.0.mfcclist;
+            let htk_param = &mut *mfcc.param;
+            param.copy_to(htk_param)?;
+            if (*self.0.jconf).preprocess.strip_zero_sample != 0 {
+                libjulius_sys::param_strip_zero(htk_param);
+            }
+            libjulius_sys::callback_exec(CallbackType::StatusParam as i32, &mut *self.0);
+        }
+        Ok(())
+    }
+
+    /// Opens an HTK parameter file (e.g. `.mfc`) as the input stream.
+    ///
+    /// Requires `-input htkparam` in the configuration and a single MFCC instance.
+    pub fn open_param_file(&mut self, path: &str) -> Result<(), anyhow::Error> {
+        self.check_param_input()?;
+        self.open_stream(Some(path))
+    }
+
+    fn check_param_input(&self) -> Result<(), anyhow::Error> {
+        let input = unsafe { &(*self.0.jconf).input };
+        check_param_input(input.speech_input, self.0.mfcclist)
+    }
+
+    /// Recognizes the opened stream until it ends.
+    ///
+    /// The messages of libjulius are passed through while recognizing, and the last ones are
+    /// kept in the [`JuliusError`] on failure.
     pub fn recognize_stream(&mut self) -> Result<(), anyhow::Error> {
-        let ret = unsafe { libjulius_sys::j_recognize_stream(&mut *self.0) };
+        let (ret, log) =
+            jlog::capture_streaming(|| unsafe { libjulius_sys::j_recognize_stream(&mut *self.0) });
         match ret {
             0 => Ok(()),
-            ret => Err(anyhow::anyhow!(ret)),
+            _ => Err(JuliusError::new("Error in recognition", log).into()),
         }
     }
 
     pub fn close_stream(&mut self) -> Result<(), anyhow::Error> {
-        let ret = unsafe { libjulius_sys::j_close_stream(&mut *self.0) };
+        let (ret, log) = jlog::capture(|| unsafe { libjulius_sys::j_close_stream(&mut *self.0) })?;
         match ret {
             0 => Ok(()),
-            ret => Err(anyhow::anyhow!(ret)),
+            _ => Err(JuliusError::new("Failed to close input stream", log).into()),
         }
     }
 
@@ -205,6 +452,58 @@ impl<'a> Recog<'a> {
         std::mem::forget(recog_wrapped);
     }
 
+    /// Adds a callback receiving the 1st pass hypotheses of the live processes.
+    ///
+    /// The callback is called with interim results while decoding (requires `-progout`),
+    /// and with the final result of the 1st pass.
+    pub fn add_callback_partial<T: FnMut(&mut Self, &[PartialResult]) + 'a>(
+        &mut self,
+        callback: T,
+    ) {
+        let callback = std::rc::Rc::new(std::cell::RefCell::new(callback));
+        let interim = callback.clone();
+        self.add_callback(CallbackType::ResultPass1Interim, move |recog| {
+            let results: Vec<_> = recog
+                .get_processes()
+                .filter(|p| p.is_live())
+                .filter_map(|p| p.partial_result())
+                .collect();
+            (interim.borrow_mut())(recog, &results);
+        });
+        self.add_callback(CallbackType::ResultPass1, move |recog| {
+            let results: Vec<_> = recog
+                .get_processes()
+                .filter(|p| p.is_live())
+                .map(|p| p.pass1_result())
+                .collect();
+            (callback.borrow_mut())(recog, &results);
+        });
+    }
+
+    /// Adds a callback receiving the position of every result in the input stream.
+    ///
+    /// The callback is called on [`CallbackType::Result`], where the results can be read from the processes.
+    /// Positions follow both input triggers and short-pause / input-side segmentation.
+    pub fn add_callback_segment<T: FnMut(&mut Self, &Segment) + 'a>(&mut self, mut callback: T) {
+        let tracker = std::rc::Rc::new(std::cell::RefCell::new(SegmentTracker::default()));
+        let t = tracker.clone();
+        self.add_callback(CallbackType::EventStreamBegin, move |_| {
+            t.borrow_mut().reset()
+        });
+        let t = tracker.clone();
+        self.add_callback(CallbackType::EventSpeechStart, move |recog| {
+            t.borrow_mut().speech_start(recog)
+        });
+        let t = tracker.clone();
+        self.add_callback(CallbackType::Result, move |recog| {
+            let segment = t.borrow_mut().segment(recog);
+            callback(recog, &segment);
+        });
+        self.add_callback(CallbackType::EventSegmentEnd, move |recog| {
+            tracker.borrow_mut().segment_end(recog)
+        });
+    }
+
     pub fn add_callback_adin<T: FnMut(&mut Self, &[i16]) + 'a>(
         &mut self,
         cb_type: AdinCallbackType,
@@ -234,7 +533,7 @@ impl<'a> Recog<'a> {
         std::mem::forget(recog_wrapped);
     }
 
-    fn add_callback_adin_inject<T: FnMut(usize) -> Option<U> + 'a, U: AsRef<[i16]>>(
+    fn add_callback_adin_inject<T: FnMut(&mut ADIn, usize) -> AdinRead<U> + 'a, U: AsRef<[i16]>>(
         &mut self,
         callback: T,
     ) {
@@ -249,7 +548,7 @@ impl<'a> Recog<'a> {
         }
     }
     unsafe extern "C" fn adin_inject_cb<
-        Env: FnMut(usize) -> Option<Result> + 'a,
+        Env: FnMut(&mut ADIn, usize) -> AdinRead<Result> + 'a,
         Result: AsRef<[i16]>,
     >(
         recog: *mut libjulius_sys::Recog,
@@ -263,7 +562,8 @@ impl<'a> Recog<'a> {
         let cnt: &mut i32 = std::mem::transmute(buf);
 
         let adin = recog_wrapped.get_adin_mut();
-        let data = ad_read(adin.samp_num() as usize);
+        let max = adin.samp_num() as usize;
+        let data = ad_read(adin, max);
         *cnt = adin.ad_read_inject_callback(data);
 
         std::mem::forget(recog_wrapped);
@@ -276,8 +576,45 @@ impl<'a> Recog<'a> {
 
 impl<'a> Drop for Recog<'a> {
     fn drop(&mut self) {
+        control::detach(self.0);
         unsafe {
             libjulius_sys::j_recog_free(&mut *self.0);
         }
     }
 }
+
+/// Checks that feature vectors can be given to the MFCC instances starting at `mfcclist`.
+fn check_param_input(
+    speech_input: i32,
+    mfcclist: *const libjulius_sys::MFCCCalc,
+) -> Result<(), anyhow::Error> {
+    if speech_input != libjulius_sys::SP_MFCFILE as i32 || mfcclist.is_null() {
+        return Err(anyhow::anyhow!(
+            "Feature vector input requires `-input htkparam`"
+        ));
+    }
+    if !unsafe { (*mfcclist).next }.is_null() {
+        return Err(anyhow::anyhow!(
+            "Feature vector input requires a single MFCC instance"
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn param_input_requires_single_mfcc() {
+        let mut first: libjulius_sys::MFCCCalc = unsafe { std::mem::zeroed() };
+        let mut second: libjulius_sys::MFCCCalc = unsafe { std::mem::zeroed() };
+        let htkparam = libjulius_sys::SP_MFCFILE as i32;
+
+        assert!(check_param_input(htkparam, &first).is_ok());
+        assert!(check_param_input(htkparam, std::ptr::null()).is_err());
+        assert!(check_param_input(libjulius_sys::SP_RAWFILE as i32, &first).is_err());
+        first.next = &mut second;
+        assert!(check_param_input(htkparam, &first).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+//! Ranked N-best hypotheses.
+//!
+//! The depth is set with [`JConf::set_nbest`](crate::JConf::set_nbest).
+
+use libjulius_sys::WORD_ID;
+
+use crate::recog_process::RecogProcess;
+
+/// Sentence hypothesis in an [`NBestList`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NBestHypothesis {
+    /// Rank starting from 1.
+    pub rank: usize,
+    pub words: Vec<String>,
+    pub word_ids: Vec<WORD_ID>,
+    pub confidence: Vec<f32>,
+    /// Total score.
+    pub score: f32,
+    pub score_am: f32,
+    pub score_lm: f32,
+    /// Score difference to the best hypothesis.
+    pub gap_to_best: f32,
+    /// Score difference to the hypothesis ranked just above, 0 for the best one.
+    pub gap_to_previous: f32,
+}
+
+/// Hypotheses of a [`RecogProcess`] ordered by score.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NBestList {
+    pub hypotheses: Vec<NBestHypothesis>,
+}
+
+impl NBestList {
+    /// Builds the list from the latest result of `process`.
+    ///
+    /// Hypotheses that differ only in transparent or silence words, anywhere in the sentence,
+    /// are merged into the higher-scored one.
+    pub fn from_process(process: &RecogProcess) -> Self {
+        let result = process.result();
+        let sentences = result.get_sent();
+        let scored: Vec<_> = sentences.iter().map(|s| (s.words(), s.score())).collect();
+        let ranked = rank(&scored, |w| {
+            process.is_transparent(w) || process.is_silence(w)
+        });
+
+        let hypotheses = ranked
+            .into_iter()
+            .enumerate()
+            .map(|(i, ranked)| {
+                let sentence = &sentences[ranked.index];
+                NBestHypothesis {
+                    rank: i + 1,
+                    words: process.word_outputs(sentence.words()),
+                    word_ids: sentence.words().to_vec(),
+                    confidence: sentence.confidence().to_vec(),
+                    score: sentence.score(),
+                    score_am: sentence.score_am(),
+                    score_lm: sentence.score_lm(),
+                    gap_to_best: ranked.gap_to_best,
+                    gap_to_previous: ranked.gap_to_previous,
+                }
+            })
+            .collect();
+        Self { hypotheses }
+    }
+
+    pub fn best(&self) -> Option<&NBestHypothesis> {
+        self.hypotheses.first()
+    }
+
+    pub fn len(&self) -> usize {
+        self.hypotheses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hypotheses.is_empty()
+    }
+}
+
+/// Sentence kept by [`rank`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Ranked {
+    /// Index in the sentences given to [`rank`].
+    index: usize,
+    gap_to_best: f32,
+    gap_to_previous: f32,
+}
+
+/// Orders `(words, score)` by score, keeping only the first of the sentences whose words are
+/// the same apart from the `ignored` ones.
+fn rank(sentences: &[(&[WORD_ID], f32)], ignored: impl Fn(WORD_ID) -> bool) -> Vec<Ranked> {
+    let mut order: Vec<usize> = (0..sentences.len()).collect();
+    order.sort_by(|&a, &b| sentences[b].1.total_cmp(&sentences[a].1));
+
+    let mut seen: Vec<Vec<WORD_ID>> = Vec::new();
+    let mut ranked: Vec<Ranked> = Vec::new();
+    for index in order {
+        let (words, score) = sentences[index];
+        let key: Vec<WORD_ID> = words.iter().copied().filter(|&w| !ignored(w)).collect();
+        if seen.contains(&key) {
+            continue;
+        }
+        seen.push(key);
+
+        let best = ranked.first().map_or(score, |r| sentences[r.index].1);
+        let previous = ranked.last().map_or(score, |r| sentences[r.index].1);
+        ranked.push(Ranked {
+            index,
+            gap_to_best: best - score,
+            gap_to_previous: previous - score,
+        });
+    }
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Silence words are 0 and 1, short pause is 9.
+    fn ignored(w: WORD_ID) -> bool {
+        matches!(w, 0 | 1 | 9)
+    }
+
+    #[test]
+    fn orders_by_score_with_gaps() {
+        let sentences: [(&[WORD_ID], f32); 3] = [
+            (&[0, 2, 1], -120.0),
+            (&[0, 3, 1], -100.0),
+            (&[0, 4, 1], -150.0),
+        ];
+        assert_eq!(
+            rank(&sentences, ignored),
+            [
+                Ranked {
+                    index: 1,
+                    gap_to_best: 0.0,
+                    gap_to_previous: 0.0
+                },
+                Ranked {
+                    index: 0,
+                    gap_to_best: 20.0,
+                    gap_to_previous: 20.0
+                },
+                Ranked {
+                    index: 2,
+                    gap_to_best: 50.0,
+                    gap_to_previous: 30.0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn merges_sentences_differing_in_ignored_words() {
+        let sentences: [(&[WORD_ID], f32); 4] = [
+            (&[0, 2, 9, 3, 1], -110.0),
+            (&[0, 2, 3, 1], -100.0),
+            (&[2, 3], -105.0),
+            (&[0, 3, 2, 1], -120.0),
+        ];
+        let ranked = rank(&sentences, ignored);
+        let indices: Vec<_> = ranked.iter().map(|r| r.index).collect();
+        // The order of the remaining words still matters
+        assert_eq!(indices, [1, 3]);
+        assert_eq!(ranked[1].gap_to_previous, 20.0);
+    }
+
+    #[test]
+    fn keeps_the_first_of_equal_scores() {
+        let sentences: [(&[WORD_ID], f32); 2] = [(&[2], -100.0), (&[9, 2], -100.0)];
+        let ranked = rank(&sentences, ignored);
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0].index, 0);
+        assert!(rank(&[], ignored).is_empty());
+    }
+}
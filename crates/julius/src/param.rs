@@ -0,0 +1,634 @@
+//! HTK parameter (feature vector) input and HTK parameter files.
+
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+/// HTK parameter kind: a base type in the lower 6 bits and qualifier flags above.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParamKind(pub u16);
+
+impl ParamKind {
+    pub const WAVEFORM: u16 = 0;
+    pub const LPC: u16 = 1;
+    pub const LPREFC: u16 = 2;
+    pub const LPCEPSTRA: u16 = 3;
+    pub const LPDELCEP: u16 = 4;
+    pub const IREFC: u16 = 5;
+    pub const MFCC: u16 = 6;
+    pub const FBANK: u16 = 7;
+    pub const MELSPEC: u16 = 8;
+    pub const USER: u16 = 9;
+    pub const DISCRETE: u16 = 10;
+    pub const PLP: u16 = 11;
+    pub const ANON: u16 = 12;
+
+    /// `_E`
+    pub const ENERGY: u16 = libjulius_sys::F_ENERGY as u16;
+    /// `_N`
+    pub const ENERGY_SUP: u16 = libjulius_sys::F_ENERGY_SUP as u16;
+    /// `_D`
+    pub const DELTA: u16 = libjulius_sys::F_DELTA as u16;
+    /// `_A`
+    pub const ACCL: u16 = libjulius_sys::F_ACCL as u16;
+    /// `_C`
+    pub const COMPRESS: u16 = libjulius_sys::F_COMPRESS as u16;
+    /// `_Z`
+    pub const CEPNORM: u16 = libjulius_sys::F_CEPNORM as u16;
+    /// `_K`
+    pub const CHECKSUM: u16 = libjulius_sys::F_CHECKSUM as u16;
+    /// `_0`
+    pub const ZEROTH: u16 = libjulius_sys::F_ZEROTH as u16;
+
+    /// `_V`
+    pub const VQ: u16 = 0x4000;
+    /// `_T`
+    pub const THIRD_DIFF: u16 = 0x8000;
+
+    pub const BASE_MASK: u16 = libjulius_sys::F_BASEMASK as u16;
+
+    const BASE_NAMES: [&str; 13] = [
+        "WAVEFORM",
+        "LPC",
+        "LPREFC",
+        "LPCEPSTRA",
+        "LPDELCEP",
+        "IREFC",
+        "MFCC",
+        "FBANK",
+        "MELSPEC",
+        "USER",
+        "DISCRETE",
+        "PLP",
+        "ANON",
+    ];
+    const QUALIFIERS: [(char, u16); 10] = [
+        ('E', Self::ENERGY),
+        ('N', Self::ENERGY_SUP),
+        ('D', Self::DELTA),
+        ('A', Self::ACCL),
+        ('T', Self::THIRD_DIFF),
+        ('C', Self::COMPRESS),
+        ('Z', Self::CEPNORM),
+        ('K', Self::CHECKSUM),
+        ('0', Self::ZEROTH),
+        ('V', Self::VQ),
+    ];
+
+    pub fn new(base: u16, qualifiers: u16) -> Self {
+        Self((base & Self::BASE_MASK) | (qualifiers & !Self::BASE_MASK))
+    }
+    pub fn base(self) -> u16 {
+        self.0 & Self::BASE_MASK
+    }
+    pub fn qualifiers(self) -> u16 {
+        self.0 & !Self::BASE_MASK
+    }
+    pub fn has(self, qualifier: u16) -> bool {
+        self.0 & qualifier != 0
+    }
+
+    /// Parses qualifier flags such as `_E_D_Z`, as `param_qualstr2code` does.
+    pub fn parse_qualifiers(s: &str) -> Result<u16, anyhow::Error> {
+        s.split('_')
+            .filter(|q| !q.is_empty())
+            .try_fold(0, |code, q| {
+                let mut chars = q.chars();
+                let qualifier = match (chars.next(), chars.next()) {
+                    (Some(c), None) => Self::QUALIFIERS
+                        .iter()
+                        .find(|(name, _)| *name == c.to_ascii_uppercase())
+                        .map(|(_, qualifier)| *qualifier),
+                    _ => None,
+                };
+                qualifier
+                    .map(|qualifier| code | qualifier)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown parameter qualifier: _{}", q))
+            })
+    }
+}
+
+impl FromStr for ParamKind {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (base, qualifiers) = s.split_at(s.find('_').unwrap_or(s.len()));
+        let base = Self::BASE_NAMES
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(base))
+            .ok_or_else(|| anyhow::anyhow!("Unknown parameter kind: {}", base))?;
+        Ok(Self::new(base as u16, Self::parse_qualifiers(qualifiers)?))
+    }
+}
+
+impl Display for ParamKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match Self::BASE_NAMES.get(self.base() as usize) {
+            Some(name) => f.write_str(name)?,
+            None => write!(f, "{}", self.base())?,
+        }
+        for (name, qualifier) in Self::QUALIFIERS {
+            if self.has(qualifier) {
+                write!(f, "_{}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Header of an HTK parameter file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HtkParamHeader {
+    /// Number of frames.
+    pub samplenum: u32,
+    /// Frame shift in 100ns units.
+    pub sample_period: u32,
+    /// Bytes per frame.
+    pub sample_size: u16,
+    pub param_kind: ParamKind,
+}
+
+impl HtkParamHeader {
+    pub const SIZE: usize = 12;
+
+    /// Reads a big-endian header.
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self, anyhow::Error> {
+        let mut buf = [0u8; Self::SIZE];
+        reader.read_exact(&mut buf)?;
+        Ok(Self::parse(&buf, ByteOrder::Big))
+    }
+
+    fn parse(buf: &[u8], order: ByteOrder) -> Self {
+        Self {
+            samplenum: order.u32(&buf[0..4]),
+            sample_period: order.u32(&buf[4..8]),
+            sample_size: order.u16(&buf[8..10]),
+            param_kind: ParamKind(order.u16(&buf[10..12])),
+        }
+    }
+
+    /// Writes a big-endian header.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), anyhow::Error> {
+        writer.write_all(&self.samplenum.to_be_bytes())?;
+        writer.write_all(&self.sample_period.to_be_bytes())?;
+        writer.write_all(&self.sample_size.to_be_bytes())?;
+        writer.write_all(&self.param_kind.0.to_be_bytes())?;
+        Ok(())
+    }
+
+    /// Length of the data following the header in bytes.
+    fn data_len(&self) -> u64 {
+        let checksum = if self.param_kind.has(ParamKind::CHECKSUM) {
+            2
+        } else {
+            0
+        };
+        self.samplenum as u64 * self.sample_size as u64 + checksum
+    }
+}
+
+/// Byte order of an HTK parameter file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteOrder {
+    Big,
+    Little,
+}
+
+impl ByteOrder {
+    fn u16(self, b: &[u8]) -> u16 {
+        let b = [b[0], b[1]];
+        match self {
+            Self::Big => u16::from_be_bytes(b),
+            Self::Little => u16::from_le_bytes(b),
+        }
+    }
+    fn u32(self, b: &[u8]) -> u32 {
+        let b = [b[0], b[1], b[2], b[3]];
+        match self {
+            Self::Big => u32::from_be_bytes(b),
+            Self::Little => u32::from_le_bytes(b),
+        }
+    }
+    fn f32(self, b: &[u8]) -> f32 {
+        f32::from_bits(self.u32(b))
+    }
+}
+
+/// Scale and offset of each dimension of compressed (`_C`) data.
+///
+/// A value `v` is stored as `round(v * scale - offset)` in 16 bits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compression {
+    pub scale: Vec<f32>,
+    pub offset: Vec<f32>,
+}
+
+/// Sequence of feature vectors, equivalent to libsent's `HTK_Param`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HtkParam {
+    /// Header of the frames as floats. `_C` and `_K` in its parameter kind select how
+    /// [`HtkParam::write`] stores them.
+    pub header: HtkParamHeader,
+    pub frames: Vec<Vec<f32>>,
+    /// Compression of the file this was read from, reused when writing `_C` data so that the
+    /// file is written back unchanged.
+    pub compression: Option<Compression>,
+}
+
+impl HtkParam {
+    /// Creates a parameter sequence, filling in the header from `frames`.
+    pub fn new(
+        param_kind: ParamKind,
+        sample_period: u32,
+        frames: Vec<Vec<f32>>,
+    ) -> Result<Self, anyhow::Error> {
+        Self::check_frames(&frames)?;
+        let veclen = frames.first().map_or(0, Vec::len);
+        let sample_size = u16::try_from(veclen * std::mem::size_of::<f32>())?;
+        Ok(Self {
+            header: HtkParamHeader {
+                samplenum: u32::try_from(frames.len())?,
+                sample_period,
+                sample_size,
+                param_kind,
+            },
+            frames,
+            compression: None,
+        })
+    }
+
+    pub fn veclen(&self) -> usize {
+        self.frames.first().map_or(0, Vec::len)
+    }
+
+    /// Fails if any frame differs in length from the first one.
+    fn check_frames(frames: &[Vec<f32>]) -> Result<(), anyhow::Error> {
+        let veclen = frames.first().map_or(0, Vec::len);
+        if frames.iter().any(|f| f.len() != veclen) {
+            return Err(anyhow::anyhow!("All frames must have the same length"));
+        }
+        Ok(())
+    }
+
+    /// Reads an HTK parameter file to the end of `reader`.
+    ///
+    /// Like libsent's `rdparam`, byte-swapped (little-endian) files are accepted: the byte order
+    /// whose header matches the length of the data is used. Compressed (`_C`) data is expanded
+    /// to floats and the checksum (`_K`) is dropped, while both qualifiers are kept in the
+    /// parameter kind so that [`HtkParam::write`] stores the frames the same way.
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self, anyhow::Error> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        if data.len() < HtkParamHeader::SIZE {
+            return Err(anyhow::anyhow!("Parameter file is too short"));
+        }
+        let (header, data) = data.split_at(HtkParamHeader::SIZE);
+        let (mut header, order) = [ByteOrder::Big, ByteOrder::Little]
+            .into_iter()
+            .map(|order| (HtkParamHeader::parse(header, order), order))
+            .find(|(header, _)| header.data_len() == data.len() as u64)
+            .ok_or_else(|| anyhow::anyhow!("Parameter file size does not match its header"))?;
+        let kind = header.param_kind;
+        if matches!(kind.base(), ParamKind::WAVEFORM | ParamKind::DISCRETE)
+            || kind.has(ParamKind::VQ)
+        {
+            return Err(anyhow::anyhow!("Unsupported parameter kind: {}", kind));
+        }
+        if header.sample_size == 0 && header.samplenum > 0 {
+            return Err(anyhow::anyhow!("Invalid sample size: 0"));
+        }
+        let width: u16 = if kind.has(ParamKind::COMPRESS) { 2 } else { 4 };
+        if !header.sample_size.is_multiple_of(width) {
+            return Err(anyhow::anyhow!(
+                "Invalid sample size: {}",
+                header.sample_size
+            ));
+        }
+        let sample_size = (header.sample_size as usize).max(1);
+
+        let mut compression = None;
+        let frames = if kind.has(ParamKind::COMPRESS) {
+            let veclen = header.sample_size as usize / 2;
+            // Scale and offset vectors occupy 4 samples
+            let samplenum = header
+                .samplenum
+                .checked_sub(4)
+                .ok_or_else(|| anyhow::anyhow!("Invalid compressed parameter file"))?;
+            let (params, data) = data.split_at(veclen * 8);
+            let (scale, offset) = params.split_at(veclen * 4);
+            let scale = read_f32_vec(scale, order);
+            let offset = read_f32_vec(offset, order);
+            header.samplenum = samplenum;
+            header.sample_size = (veclen * 4) as u16;
+            let frames = data
+                .chunks_exact(sample_size)
+                .take(samplenum as usize)
+                .map(|frame| {
+                    frame[..veclen * 2]
+                        .chunks_exact(2)
+                        .zip(scale.iter().zip(&offset))
+                        .map(|(v, (a, b))| (order.u16(v) as i16 as f32 + b) / a)
+                        .collect()
+                })
+                .collect();
+            compression = Some(Compression { scale, offset });
+            frames
+        } else {
+            let veclen = header.sample_size as usize / 4;
+            data.chunks_exact(sample_size)
+                .take(header.samplenum as usize)
+                .map(|frame| read_f32_vec(&frame[..veclen * 4], order))
+                .collect()
+        };
+        Ok(Self {
+            header,
+            frames,
+            compression,
+        })
+    }
+
+    pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Self, anyhow::Error> {
+        Self::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Writes a big-endian HTK parameter file.
+    ///
+    /// `_C` in the parameter kind compresses the frames into 16-bit integers with
+    /// [`HtkParam::compression`], or a scale and an offset fitting the range of each dimension
+    /// as HTK does. `_K` appends a CRC-16 checksum of the data.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), anyhow::Error> {
+        let kind = self.header.param_kind;
+        Self::check_frames(&self.frames)?;
+        if self.header.samplenum as usize != self.frames.len()
+            || self.header.sample_size as usize != self.veclen() * 4
+        {
+            return Err(anyhow::anyhow!("Header does not match the frames"));
+        }
+
+        let mut header = self.header;
+        let mut data = Vec::new();
+        if kind.has(ParamKind::COMPRESS) {
+            header.samplenum = header
+                .samplenum
+                .checked_add(4)
+                .ok_or_else(|| anyhow::anyhow!("Too many frames"))?;
+            header.sample_size /= 2;
+            let compression = match &self.compression {
+                Some(compression) if compression.scale.len() == self.veclen() => {
+                    compression.clone()
+                }
+                _ => self.fit_compression(),
+            };
+            let Compression { scale, offset } = &compression;
+            for v in scale.iter().chain(offset) {
+                data.extend_from_slice(&v.to_be_bytes());
+            }
+            for frame in &self.frames {
+                for (v, (a, b)) in frame.iter().zip(scale.iter().zip(offset)) {
+                    let v = (v * a - b).round().clamp(-32767.0, 32767.0) as i16;
+                    data.extend_from_slice(&v.to_be_bytes());
+                }
+            }
+        } else {
+            for frame in &self.frames {
+                for v in frame {
+                    data.extend_from_slice(&v.to_be_bytes());
+                }
+            }
+        }
+        if kind.has(ParamKind::CHECKSUM) {
+            data.extend_from_slice(&crc16(&data).to_be_bytes());
+        }
+        header.write(writer)?;
+        writer.write_all(&data)?;
+        Ok(())
+    }
+
+    /// Maps the range of each dimension onto `[-32767, 32767]`.
+    fn fit_compression(&self) -> Compression {
+        let (scale, offset) = (0..self.veclen())
+            .map(|i| {
+                let (min, max) = self
+                    .frames
+                    .iter()
+                    .map(|frame| frame[i])
+                    .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+                        (min.min(v), max.max(v))
+                    });
+                if max > min {
+                    let range = max - min;
+                    (2.0 * 32767.0 / range, (max + min) * 32767.0 / range)
+                } else {
+                    // Constant dimension, stored as zeros
+                    (1.0, max)
+                }
+            })
+            .unzip();
+        Compression { scale, offset }
+    }
+
+    pub fn write_file<P: AsRef<Path>>(&self, path: P) -> Result<(), anyhow::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Copies the frames into a libsent `HTK_Param`.
+    pub(crate) fn copy_to(
+        &self,
+        param: &mut libjulius_sys::HTK_Param,
+    ) -> Result<(), anyhow::Error> {
+        // Every frame is copied into a vector of the first one's length
+        Self::check_frames(&self.frames)?;
+        let veclen = i16::try_from(self.veclen())?;
+        let samplenum = u32::try_from(self.frames.len())?;
+        unsafe {
+            libjulius_sys::param_init_content(param);
+            if libjulius_sys::param_alloc(param, samplenum, veclen) == 0 {
+                return Err(anyhow::anyhow!("Failed to allocate parameter"));
+            }
+            for (t, frame) in self.frames.iter().enumerate() {
+                let vec = *param.parvec.add(t);
+                vec.copy_from(frame.as_ptr(), frame.len());
+            }
+        }
+        param.header = libjulius_sys::HTK_Param_Header {
+            samplenum,
+            wshift: self.header.sample_period,
+            sampsize: self.header.sample_size,
+            // The frames are neither compressed nor followed by a checksum in memory
+            samptype: (self.header.param_kind.0 & !(ParamKind::COMPRESS | ParamKind::CHECKSUM))
+                as i16,
+        };
+        param.samplenum = samplenum;
+        param.veclen = veclen;
+        Ok(())
+    }
+}
+
+fn read_f32_vec(buf: &[u8], order: ByteOrder) -> Vec<f32> {
+    buf.chunks_exact(4).map(|b| order.f32(b)).collect()
+}
+
+/// CRC-16/CCITT of the data, used for `_K`.
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ (byte as u16) << 8, |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(qualifiers: u16) -> HtkParam {
+        let frames = (0..20)
+            .map(|t| {
+                (0..5)
+                    .map(|i| ((t * 7 + i * 3) % 11) as f32 * 0.37 - 1.5 + i as f32)
+                    .collect()
+            })
+            .collect();
+        HtkParam::new(ParamKind::new(ParamKind::MFCC, qualifiers), 100000, frames).unwrap()
+    }
+
+    fn write(param: &HtkParam) -> Vec<u8> {
+        let mut buf = Vec::new();
+        param.write(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn round_trip_is_byte_exact() {
+        for qualifiers in [
+            ParamKind::ENERGY | ParamKind::DELTA,
+            ParamKind::CHECKSUM,
+            ParamKind::COMPRESS,
+            ParamKind::COMPRESS | ParamKind::CHECKSUM,
+        ] {
+            let original = param(qualifiers);
+            let first = write(&original);
+            let read = HtkParam::read(&mut first.as_slice()).unwrap();
+            assert_eq!(read.header, original.header);
+            assert_eq!(write(&read), first, "{}", original.header.param_kind);
+        }
+    }
+
+    #[test]
+    fn file_layout() {
+        let bytes = write(&param(ParamKind::COMPRESS | ParamKind::CHECKSUM));
+        // 4 extra samples of scale and offset, 2 bytes per value
+        assert_eq!(
+            &bytes[..12],
+            &[0, 0, 0, 24, 0, 1, 0x86, 0xa0, 0, 10, 0x14, 0x06]
+        );
+        assert_eq!(bytes.len(), 12 + 24 * 10 + 2);
+        let crc = crc16(&bytes[12..bytes.len() - 2]);
+        assert_eq!(&bytes[bytes.len() - 2..], &crc.to_be_bytes());
+        assert_eq!(crc16(b"123456789"), 0x31c3);
+    }
+
+    #[test]
+    fn compression_keeps_values() {
+        let original = param(ParamKind::COMPRESS);
+        let read = HtkParam::read(&mut write(&original).as_slice()).unwrap();
+        for (a, b) in original
+            .frames
+            .iter()
+            .flatten()
+            .zip(read.frames.iter().flatten())
+        {
+            assert!((a - b).abs() < 1e-3, "{} {}", a, b);
+        }
+
+        let constant = HtkParam::new(
+            ParamKind::new(ParamKind::USER, ParamKind::COMPRESS),
+            1,
+            vec![vec![2.5]; 3],
+        )
+        .unwrap();
+        let read = HtkParam::read(&mut write(&constant).as_slice()).unwrap();
+        assert_eq!(read.frames, vec![vec![2.5]; 3]);
+    }
+
+    #[test]
+    fn reads_byte_swapped_files() {
+        for qualifiers in [ParamKind::ENERGY, ParamKind::COMPRESS | ParamKind::CHECKSUM] {
+            let original = param(qualifiers);
+            let big = write(&original);
+            // Swap every field of the header and every value of the data
+            let mut little = Vec::new();
+            for field in [&big[0..4], &big[4..8], &big[8..10], &big[10..12]] {
+                little.extend(field.iter().rev());
+            }
+            let width = if qualifiers & ParamKind::COMPRESS != 0 {
+                2
+            } else {
+                4
+            };
+            let data = &big[12..big.len()
+                - if qualifiers & ParamKind::CHECKSUM != 0 {
+                    2
+                } else {
+                    0
+                }];
+            let (params, values) = data.split_at(if width == 2 { 5 * 8 } else { 0 });
+            for value in params.chunks(4).chain(values.chunks(width)) {
+                little.extend(value.iter().rev());
+            }
+            little.resize(big.len(), 0);
+
+            let read_big = HtkParam::read(&mut big.as_slice()).unwrap();
+            let read_little = HtkParam::read(&mut little.as_slice()).unwrap();
+            assert_eq!(read_big, read_little);
+        }
+    }
+
+    #[test]
+    fn rejects_inconsistent_files() {
+        let mut bytes = write(&param(0));
+        bytes.pop();
+        assert!(HtkParam::read(&mut bytes.as_slice()).is_err());
+        assert!(HtkParam::read(&mut &bytes[..8]).is_err());
+
+        // 2 frames of 6 bytes, not a whole number of floats
+        let header = HtkParamHeader {
+            samplenum: 2,
+            sample_period: 100000,
+            sample_size: 6,
+            param_kind: ParamKind::new(ParamKind::USER, 0),
+        };
+        let mut bytes = Vec::new();
+        header.write(&mut bytes).unwrap();
+        bytes.extend([0; 12]);
+        assert!(HtkParam::read(&mut bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn rejects_frames_of_different_lengths() {
+        let mut param = param(0);
+        param.frames[3].push(0.0);
+        assert!(param.write(&mut Vec::new()).is_err());
+        param.frames[3].truncate(4);
+        assert!(param.write(&mut Vec::new()).is_err());
+        assert!(HtkParam::new(param.header.param_kind, 1, param.frames).is_err());
+    }
+
+    #[test]
+    fn parses_kinds() {
+        let kind: ParamKind = "MFCC_E_D_A_Z".parse().unwrap();
+        assert_eq!(kind.base(), ParamKind::MFCC);
+        assert_eq!(kind.to_string(), "MFCC_E_D_A_Z");
+        assert!("MFCC_X".parse::<ParamKind>().is_err());
+    }
+}
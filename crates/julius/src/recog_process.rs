@@ -1,9 +1,16 @@
+use std::{
+    ffi::{c_char, CStr},
+    str::Utf8Error,
+};
+
+use libjulius_sys::WORD_ID;
+
 use strum_macros::FromRepr;
 
-use crate::{iter::BindIterator, sentence_align::SentenceAlign};
+use crate::{iter::BindIterator, sentence_align::SentenceAlign, word_graph::WordGraph};
 
 #[repr(i32)]
-#[derive(Debug, Clone, Copy, FromRepr)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
 pub enum ResultStatus {
     RejectLong = libjulius_sys::J_RESULT_STATUS_REJECT_LONG,
     BufferOverflow = libjulius_sys::J_RESULT_STATUS_BUFFER_OVERFLOW,
@@ -38,15 +45,129 @@ impl RecogProcess {
         let result = self.0.result;
         Output(result)
     }
+    /// Returns whether the 1st pass has an interim result.
+    pub fn has_interim(&self) -> bool {
+        self.0.have_interim != 0
+    }
+    /// Word sequence of the best hypothesis at the end of the 1st pass.
+    pub fn pass1_words(&self) -> &[WORD_ID] {
+        &self.0.pass1_wseq[..self.0.pass1_wnum as usize]
+    }
+    /// Score of the best hypothesis at the end of the 1st pass.
+    pub fn pass1_score(&self) -> f32 {
+        self.0.pass1_score
+    }
+    /// Returns the current best hypothesis of the 1st pass, or `None` if there is none yet.
+    ///
+    /// Interim results are available in [`CallbackType::ResultPass1Interim`](crate::CallbackType::ResultPass1Interim)
+    /// when progressive output (`-progout`) is enabled.
+    pub fn partial_result(&self) -> Option<PartialResult> {
+        if !self.has_interim() {
+            return None;
+        }
+        let result = self.result();
+        let pass1 = result.pass1();
+        Some(PartialResult::new(
+            self,
+            pass1.words(),
+            pass1.score(),
+            false,
+        ))
+    }
+    /// Returns the final result of the 1st pass, available from [`CallbackType::ResultPass1`](crate::CallbackType::ResultPass1).
+    pub fn pass1_result(&self) -> PartialResult {
+        PartialResult::new(self, self.pass1_words(), self.pass1_score(), true)
+    }
+    /// Returns the output string of the word `w` in the dictionary of this process.
+    pub fn word_output(&self, w: WORD_ID) -> Option<Result<String, Utf8Error>> {
+        let winfo = self.winfo()?;
+        Self::word_string(winfo, winfo.woutput, w)
+    }
+    /// Returns the name of the word `w` in the dictionary of this process.
+    pub fn word_name(&self, w: WORD_ID) -> Option<Result<String, Utf8Error>> {
+        let winfo = self.winfo()?;
+        Self::word_string(winfo, winfo.wname, w)
+    }
+    /// Returns whether the word `w` is transparent (skipped by the LM context).
+    pub fn is_transparent(&self, w: WORD_ID) -> bool {
+        let Some(winfo) = self.winfo() else {
+            return false;
+        };
+        let Some(index) = Self::word_index(winfo, w) else {
+            return false;
+        };
+        !winfo.is_transparent.is_null() && unsafe { *winfo.is_transparent.add(index) != 0 }
+    }
+    /// Returns whether the word `w` is the head or tail silence word, or a short pause word
+    /// consisting only of the short pause model (`-spmodel`).
+    pub fn is_silence(&self, w: WORD_ID) -> bool {
+        let Some(winfo) = self.winfo() else {
+            return false;
+        };
+        if w == winfo.head_silwid || w == winfo.tail_silwid {
+            return true;
+        }
+        let Some(index) = Self::word_index(winfo, w) else {
+            return false;
+        };
+        if winfo.wlen.is_null() || winfo.wseq.is_null() {
+            return false;
+        }
+        let Some(sp) = (unsafe { self.0.am.as_ref() })
+            .and_then(|am| unsafe { am.hmminfo.as_ref() })
+            .map(|hmminfo| hmminfo.sp)
+            .filter(|sp| !sp.is_null())
+        else {
+            return false;
+        };
+        unsafe { *winfo.wlen.add(index) == 1 && **winfo.wseq.add(index) == sp }
+    }
+    /// Index of `w` in the arrays of `winfo`, or `None` if it is out of range.
+    fn word_index(winfo: &libjulius_sys::WORD_INFO, w: WORD_ID) -> Option<usize> {
+        // WORD_ID is signed with `words-int`, so negative IDs wrap out of range
+        let index = w as usize;
+        (index < winfo.num as usize).then_some(index)
+    }
+    fn winfo(&self) -> Option<&libjulius_sys::WORD_INFO> {
+        let lm = unsafe { self.0.lm.as_ref()? };
+        unsafe { lm.winfo.as_ref() }
+    }
+    fn word_string(
+        winfo: &libjulius_sys::WORD_INFO,
+        strings: *mut *mut c_char,
+        w: WORD_ID,
+    ) -> Option<Result<String, Utf8Error>> {
+        let index = Self::word_index(winfo, w)?;
+        if strings.is_null() {
+            return None;
+        }
+        let string = unsafe { *strings.add(index) };
+        if string.is_null() {
+            return None;
+        }
+        let cstr = unsafe { CStr::from_ptr(string) };
+        Some(cstr.to_str().map(|s| s.to_string()))
+    }
+    /// Returns the output strings of `words`, replacing unknown words with empty strings.
+    pub fn word_outputs(&self, words: &[WORD_ID]) -> Vec<String> {
+        words
+            .iter()
+            .map(|&w| self.word_output(w).and_then(Result::ok).unwrap_or_default())
+            .collect()
+    }
 }
 
 #[derive(Debug)]
-pub struct Output(libjulius_sys::Output);
+pub struct Output(pub(crate) libjulius_sys::Output);
 
 impl Output {
     pub fn status(&self) -> ResultStatus {
         ResultStatus::from_repr(self.0.status).unwrap()
     }
+    /// Number of frames of the input.
+    pub fn num_frame(&self) -> i32 {
+        self.0.num_frame
+    }
     pub fn get_sent(&self) -> &[Sentence] {
         unsafe {
             let s = std::slice::from_raw_parts(self.0.sent, self.0.sentnum as usize);
@@ -55,12 +176,61 @@ impl Output {
             body
         }
     }
+    /// Word graph of the 2nd pass.
+    pub fn word_graph(&self) -> Option<WordGraph<'_>> {
+        WordGraph::new(self.0.wg)
+    }
+    /// Word graph generated on the 1st pass.
+    pub fn pass1_word_graph(&self) -> Option<WordGraph<'_>> {
+        WordGraph::new(self.0.wg1)
+    }
+    /// Result of the 1st pass.
+    pub fn pass1(&self) -> &Sentence {
+        unsafe { &*(&self.0.pass1 as *const libjulius_sys::Sentence as *const Sentence) }
+    }
+}
+
+/// Owned hypothesis of the 1st pass.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialResult {
+    pub words: Vec<String>,
+    pub word_ids: Vec<WORD_ID>,
+    pub score: f32,
+    /// Whether this is the final result of the 1st pass.
+    pub is_final: bool,
+}
+
+impl PartialResult {
+    fn new(process: &RecogProcess, word_ids: &[WORD_ID], score: f32, is_final: bool) -> Self {
+        Self {
+            words: process.word_outputs(word_ids),
+            word_ids: word_ids.to_vec(),
+            score,
+            is_final,
+        }
+    }
 }
 
 #[derive(Debug)]
+#[repr(transparent)]
 pub struct Sentence(libjulius_sys::Sentence);
 
 impl Sentence {
+    pub fn words(&self) -> &[WORD_ID] {
+        &self.0.word[..self.0.word_num as usize]
+    }
+    pub fn confidence(&self) -> &[f32] {
+        &self.0.confidence[..self.0.word_num as usize]
+    }
+    pub fn score(&self) -> f32 {
+        self.0.score
+    }
+    pub fn score_am(&self) -> f32 {
+        self.0.score_am
+    }
+    pub fn score_lm(&self) -> f32 {
+        self.0.score_lm
+    }
     pub fn get_align(&self) -> BindIterator<libjulius_sys::SentenceAlign, SentenceAlign> {
         BindIterator::new(
             self.0.align,
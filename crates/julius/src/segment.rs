@@ -0,0 +1,115 @@
+//! Absolute position of recognition results in the input stream.
+//!
+//! Frame numbers in results and alignments are relative to the segment being decoded.
+//! A [`Segment`] places them on the timeline of the whole input, counted in samples
+//! from the beginning of the stream.
+
+use std::ops::Range;
+
+use crate::{recog_process::Sentence, sentence_align::SentenceAlignWithType, Recog};
+
+/// Input span of a recognition result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    /// First sample of the segment.
+    pub start_sample: u64,
+    /// Sample just after the last frame of the segment.
+    pub end_sample: u64,
+    /// Frame shift in samples.
+    pub frameshift: u32,
+}
+
+impl Segment {
+    /// Absolute sample at which the frame `frame` of this segment begins.
+    pub fn frame_to_sample(&self, frame: i32) -> u64 {
+        self.start_sample + frame.max(0) as u64 * self.frameshift as u64
+    }
+
+    /// Absolute sample range of the frames `begin_frame..=end_frame`.
+    pub fn frame_range(&self, begin_frame: i32, end_frame: i32) -> Range<u64> {
+        self.frame_to_sample(begin_frame)..self.frame_to_sample(end_frame + 1)
+    }
+
+    /// Absolute spans of the words of `sentence`, available with word alignment (`-walign`).
+    pub fn word_alignments(&self, sentence: &Sentence) -> Vec<TimedWord> {
+        let mut words = Vec::new();
+        for align in sentence.get_align() {
+            if let SentenceAlignWithType::Word(frames) = align.t() {
+                words.extend(frames.frame_iter().map(|f| TimedWord {
+                    word_id: f.w,
+                    samples: self.frame_range(f.begin_frame, f.end_frame),
+                    avgscore: f.avgscore,
+                }));
+            }
+        }
+        words
+    }
+}
+
+/// Word aligned to the input stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedWord {
+    pub word_id: libjulius_sys::WORD_ID,
+    /// Absolute sample range of the word.
+    pub samples: Range<u64>,
+    pub avgscore: f32,
+}
+
+/// Follows triggers and segmentation to find where the current segment begins.
+#[derive(Debug, Default)]
+pub(crate) struct SegmentTracker {
+    trigger_start: u64,
+    /// Frames consumed by the previous segments of the current trigger.
+    frame_offset: u64,
+    last_num_frame: u64,
+}
+
+impl SegmentTracker {
+    pub(crate) fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Called when the input is triggered.
+    pub(crate) fn speech_start(&mut self, recog: &Recog) {
+        self.follow_trigger(recog);
+    }
+
+    fn follow_trigger(&mut self, recog: &Recog) {
+        let trigger = recog.adin().last_trigger_sample();
+        if trigger != self.trigger_start {
+            self.trigger_start = trigger;
+            self.frame_offset = 0;
+        }
+    }
+
+    /// Called on every result.
+    pub(crate) fn segment(&mut self, recog: &Recog) -> Segment {
+        self.follow_trigger(recog);
+        let frameshift = recog.frameshift();
+        self.last_num_frame = recog
+            .get_processes()
+            .filter(|p| p.is_live())
+            .map(|p| p.result().num_frame())
+            .max()
+            .unwrap_or(0) as u64;
+        let start_sample = self.trigger_start + self.frame_offset * frameshift as u64;
+        Segment {
+            start_sample,
+            end_sample: start_sample + self.last_num_frame * frameshift as u64,
+            frameshift,
+        }
+    }
+
+    /// Called at the end of a segment.
+    pub(crate) fn segment_end(&mut self, recog: &Recog) {
+        let mfcc = unsafe { recog.0.mfcclist.as_ref() };
+        match mfcc {
+            // Short-pause segmentation resumes from the beginning of the pause
+            Some(mfcc) if mfcc.segmented != 0 && mfcc.segmented_by_input == 0 => {
+                self.frame_offset += mfcc.sparea_start.max(0) as u64;
+            }
+            _ => self.frame_offset += self.last_num_frame,
+        }
+        self.last_num_frame = 0;
+    }
+}
@@ -101,7 +101,7 @@ pub struct SentenceAlignFrameWord {
     pub begin_frame: i32,
     pub end_frame: i32,
     pub avgscore: f32,
-    pub w: i32,
+    pub w: libjulius_sys::WORD_ID,
 }
 
 #[derive(Debug)]
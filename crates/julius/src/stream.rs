@@ -0,0 +1,344 @@
+//! Async facade for streaming recognition on tokio.
+//!
+//! libjulius decodes synchronously inside [`Recog::recognize_stream`], pulling audio through the
+//! ADIn inject callback. [`spawn`] moves the decoder onto a dedicated thread so that async code
+//! can push audio chunks with [`AudioSender::send`] and consume [`RecognitionEvent`]s from an
+//! [`EventStream`].
+//!
+//! Only the audio is bounded: when the decoder falls behind, [`AudioSender::send`] waits until
+//! it catches up. Events are queued without bound, so the decoder never waits for the
+//! [`EventStream`], and sending audio without reading the events cannot deadlock.
+
+use std::{
+    num::NonZeroUsize,
+    pin::Pin,
+    task::{Context, Poll},
+    thread,
+};
+
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    recog_process::{PartialResult, RecogProcess, ResultStatus},
+    segment::Segment,
+    CallbackType, Recog,
+};
+
+/// Event emitted by the decoder thread.
+#[derive(Debug, Clone)]
+pub enum RecognitionEvent {
+    SpeechStart,
+    SpeechStop,
+    /// Hypotheses of the 1st pass of every live process.
+    Partial(Vec<PartialResult>),
+    /// Recognition results of every live process.
+    Result(Vec<ProcessResult>),
+}
+
+/// Owned recognition result of a single [`RecogProcess`].
+#[derive(Debug, Clone)]
+pub struct ProcessResult {
+    pub status: ResultStatus,
+    pub sentences: Vec<Hypothesis>,
+    /// Position in the input stream, set on results of [`spawn`].
+    pub segment: Option<Segment>,
+}
+
+impl ProcessResult {
+    pub fn from_process(process: &RecogProcess) -> Self {
+        let result = process.result();
+        let sentences = result
+            .get_sent()
+            .iter()
+            .map(|s| Hypothesis {
+                words: process.word_outputs(s.words()),
+                word_ids: s.words().to_vec(),
+                confidence: s.confidence().to_vec(),
+                score: s.score(),
+            })
+            .collect();
+        Self {
+            status: result.status(),
+            sentences,
+            segment: None,
+        }
+    }
+}
+
+/// Owned sentence hypothesis.
+#[derive(Debug, Clone)]
+pub struct Hypothesis {
+    pub words: Vec<String>,
+    pub word_ids: Vec<libjulius_sys::WORD_ID>,
+    pub confidence: Vec<f32>,
+    pub score: f32,
+}
+
+/// Sending half of the audio channel.
+///
+/// Dropping every sender ends the input stream.
+#[derive(Debug, Clone)]
+pub struct AudioSender(mpsc::Sender<Vec<i16>>);
+
+impl AudioSender {
+    /// Pushes a chunk of samples to the decoder, waiting while the decoder is behind.
+    ///
+    /// This does not depend on the [`EventStream`] being read.
+    pub async fn send(&self, chunk: Vec<i16>) -> Result<(), anyhow::Error> {
+        self.0
+            .send(chunk)
+            .await
+            .map_err(|_| anyhow::anyhow!("Decoder has stopped"))
+    }
+}
+
+/// Receiving half of the event channel.
+#[derive(Debug)]
+pub struct EventStream(mpsc::UnboundedReceiver<RecognitionEvent>);
+
+impl EventStream {
+    /// Receives the next event, or `None` after the decoder has stopped.
+    pub async fn recv(&mut self) -> Option<RecognitionEvent> {
+        self.0.recv().await
+    }
+}
+
+impl futures_core::Stream for EventStream {
+    type Item = RecognitionEvent;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.poll_recv(cx)
+    }
+}
+
+/// Handle to the decoder thread.
+#[derive(Debug)]
+pub struct DecoderHandle(oneshot::Receiver<Result<(), anyhow::Error>>);
+
+impl DecoderHandle {
+    /// Waits until the decoder thread exits and returns its result.
+    pub async fn join(self) -> Result<(), anyhow::Error> {
+        self.0
+            .await
+            .map_err(|_| anyhow::anyhow!("Decoder thread panicked"))?
+    }
+}
+
+/// Spawns a decoder thread.
+///
+/// `build` is called on the decoder thread, since [`Recog`] cannot be moved across threads.
+/// `capacity` is the number of audio chunks that may be queued. Events are queued without
+/// bound, so an [`EventStream`] that is never read keeps every event in memory until it is
+/// dropped.
+pub fn spawn<F>(
+    build: F,
+    capacity: NonZeroUsize,
+) -> Result<(AudioSender, EventStream, DecoderHandle), anyhow::Error>
+where
+    F: FnOnce() -> Result<Recog<'static>, anyhow::Error> + Send + 'static,
+{
+    let (audio_tx, audio_rx) = mpsc::channel(capacity.get());
+    let (event_tx, event_rx) = mpsc::unbounded_channel();
+    let (done_tx, done_rx) = oneshot::channel();
+
+    thread::Builder::new()
+        .name("julius-decoder".to_string())
+        .spawn(move || {
+            let result = run(build, audio_rx, event_tx);
+            let _ = done_tx.send(result);
+        })
+        .map_err(|e| anyhow::anyhow!("Failed to spawn decoder thread: {}", e))?;
+
+    Ok((
+        AudioSender(audio_tx),
+        EventStream(event_rx),
+        DecoderHandle(done_rx),
+    ))
+}
+
+fn run<F>(
+    build: F,
+    audio_rx: mpsc::Receiver<Vec<i16>>,
+    event_tx: mpsc::UnboundedSender<RecognitionEvent>,
+) -> Result<(), anyhow::Error>
+where
+    F: FnOnce() -> Result<Recog<'static>, anyhow::Error>,
+{
+    let mut recog = build()?;
+
+    let tx = event_tx.clone();
+    recog.add_callback(CallbackType::EventSpeechStart, move |_| {
+        let _ = tx.send(RecognitionEvent::SpeechStart);
+    });
+    let tx = event_tx.clone();
+    recog.add_callback(CallbackType::EventSpeechStop, move |_| {
+        let _ = tx.send(RecognitionEvent::SpeechStop);
+    });
+    let tx = event_tx.clone();
+    recog.add_callback_partial(move |_, results| {
+        let _ = tx.send(RecognitionEvent::Partial(results.to_vec()));
+    });
+    let tx = event_tx;
+    recog.add_callback_segment(move |recog, segment| {
+        let results = recog
+            .get_processes()
+            .filter(|p| p.is_live())
+            .map(|p| ProcessResult {
+                segment: Some(*segment),
+                ..ProcessResult::from_process(&p)
+            })
+            .collect();
+        let _ = tx.send(RecognitionEvent::Result(results));
+    });
+
+    recog.adin_init()?;
+
+    let mut reader = ChunkReader::new(audio_rx);
+    recog.custom_adin(move |max| reader.read(max));
+
+    recog.open_stream(None)?;
+    recog.recognize_stream()?;
+    recog.close_stream()?;
+
+    Ok(())
+}
+
+/// Splits the chunks from [`AudioSender`] into reads of the ADIn callback.
+struct ChunkReader {
+    rx: mpsc::Receiver<Vec<i16>>,
+    pending: Vec<i16>,
+}
+
+impl ChunkReader {
+    fn new(rx: mpsc::Receiver<Vec<i16>>) -> Self {
+        Self {
+            rx,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Returns up to `max` samples, or `None` after every sender has been dropped.
+    fn read(&mut self, max: usize) -> Option<Vec<i16>> {
+        while self.pending.is_empty() {
+            self.pending = self.rx.blocking_recv()?;
+        }
+        let len = max.min(self.pending.len());
+        Some(self.pending.drain(..len).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use super::*;
+
+    fn channel(capacity: usize) -> (AudioSender, ChunkReader) {
+        let (tx, rx) = mpsc::channel(capacity);
+        (AudioSender(tx), ChunkReader::new(rx))
+    }
+
+    fn send_now(sender: &AudioSender, chunk: Vec<i16>) {
+        let mut cx = Context::from_waker(Waker::noop());
+        let poll = pin!(sender.send(chunk)).poll(&mut cx);
+        assert!(matches!(poll, Poll::Ready(Ok(()))));
+    }
+
+    #[test]
+    fn chunks_are_read_in_order() {
+        let (sender, mut reader) = channel(4);
+        send_now(&sender, vec![1, 2, 3]);
+        send_now(&sender, vec![]);
+        send_now(&sender, vec![4, 5]);
+
+        assert_eq!(reader.read(2), Some(vec![1, 2]));
+        assert_eq!(reader.read(2), Some(vec![3]));
+        assert_eq!(reader.read(8), Some(vec![4, 5]));
+    }
+
+    #[test]
+    fn dropping_senders_ends_stream() {
+        let (sender, mut reader) = channel(4);
+        let other = sender.clone();
+        send_now(&sender, vec![1, 2]);
+        drop(sender);
+        send_now(&other, vec![3]);
+        drop(other);
+
+        assert_eq!(reader.read(8), Some(vec![1, 2]));
+        assert_eq!(reader.read(8), Some(vec![3]));
+        assert_eq!(reader.read(8), None);
+    }
+
+    #[test]
+    fn send_waits_while_channel_is_full() {
+        let (sender, mut reader) = channel(1);
+        send_now(&sender, vec![1]);
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut send = pin!(sender.send(vec![2]));
+        assert!(send.as_mut().poll(&mut cx).is_pending());
+
+        assert_eq!(reader.read(8), Some(vec![1]));
+        assert!(matches!(send.as_mut().poll(&mut cx), Poll::Ready(Ok(()))));
+        assert_eq!(reader.read(8), Some(vec![2]));
+    }
+
+    /// Polls `future` on the current thread until it completes.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct Unpark(thread::Thread);
+        impl std::task::Wake for Unpark {
+            fn wake(self: std::sync::Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+        let waker = Waker::from(std::sync::Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    #[test]
+    fn send_does_not_wait_for_events() {
+        let (sender, mut reader) = channel(1);
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        // Decoder emitting an event for every read, like the callbacks of `run`
+        let decoder = thread::spawn(move || {
+            while reader.read(8).is_some() {
+                event_tx.send(RecognitionEvent::SpeechStart).unwrap();
+            }
+        });
+
+        // The event stream is not read until the input ends
+        let mut events = EventStream(event_rx);
+        for i in 0..100 {
+            block_on(sender.send(vec![i])).unwrap();
+        }
+        drop(sender);
+        decoder.join().unwrap();
+
+        let mut received = 0;
+        while block_on(events.recv()).is_some() {
+            received += 1;
+        }
+        assert_eq!(received, 100);
+    }
+
+    #[test]
+    fn send_fails_after_decoder_stops() {
+        let (sender, reader) = channel(1);
+        drop(reader);
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let poll = pin!(sender.send(vec![1])).poll(&mut cx);
+        assert!(matches!(poll, Poll::Ready(Err(_))));
+    }
+}
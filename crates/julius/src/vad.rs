@@ -0,0 +1,474 @@
+//! Voice activity detection in front of the custom ADIn path.
+//!
+//! The custom ADIn path disables libjulius' own level and zero-cross detection, so
+//! [`VadSource`] cuts the input on the Rust side instead: non-speech is dropped, the end of
+//! each utterance is sent as [`AdinRead::EndOfSegment`], and [`VadEvent`]s report where
+//! speech starts and stops in the original input.
+
+use std::collections::VecDeque;
+
+use crate::adin::{AdinRead, AudioSource, InputFormat};
+
+mod band_snr;
+mod energy;
+#[cfg(feature = "fvad")]
+mod fvad;
+
+pub use band_snr::BandSnrVad;
+pub use energy::EnergyVad;
+#[cfg(feature = "fvad")]
+pub use fvad::FvadVad;
+
+/// Aggressiveness of `FvadVad` and [`BandSnrVad`], libfvad's modes 0 to 3 (`-fvad`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VadMode {
+    #[default]
+    Quality = 0,
+    LowBitrate = 1,
+    Aggressive = 2,
+    VeryAggressive = 3,
+}
+
+/// Frame-wise speech / non-speech classifier.
+pub trait Vad {
+    /// Number of samples in a frame given to [`Vad::is_speech`].
+    fn frame_len(&self) -> usize;
+    fn is_speech(&mut self, frame: &[i16]) -> bool;
+    /// Forgets the state adapted to the previous input. [`VadSource`] calls this when the
+    /// stream starts.
+    fn reset(&mut self) {}
+}
+
+impl<V: Vad + ?Sized> Vad for Box<V> {
+    fn frame_len(&self) -> usize {
+        (**self).frame_len()
+    }
+    fn is_speech(&mut self, frame: &[i16]) -> bool {
+        (**self).is_speech(frame)
+    }
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+}
+
+/// [`Vad`] delegating the decision to a closure, e.g. a user-supplied model.
+pub struct FnVad<F: FnMut(&[i16]) -> bool> {
+    frame_len: usize,
+    is_speech: F,
+}
+
+impl<F: FnMut(&[i16]) -> bool> FnVad<F> {
+    pub fn new(frame_len: usize, is_speech: F) -> Self {
+        Self {
+            frame_len,
+            is_speech,
+        }
+    }
+}
+
+impl<F: FnMut(&[i16]) -> bool> Vad for FnVad<F> {
+    fn frame_len(&self) -> usize {
+        self.frame_len
+    }
+    fn is_speech(&mut self, frame: &[i16]) -> bool {
+        (self.is_speech)(frame)
+    }
+}
+
+/// Transition detected by a [`VadSource`].
+///
+/// Offsets are counted in samples of the wrapped source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VadEvent {
+    /// Speech starts at `sample`, including the head margin.
+    SpeechStart { sample: u64 },
+    /// Speech ends just before `sample`, including the tail margin.
+    SpeechStop { sample: u64 },
+}
+
+/// Timing parameters of [`VadSource`], equivalent to `-headmargin` and `-tailmargin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VadConfig {
+    /// Audio kept before the trigger, in milliseconds.
+    pub head_margin_ms: u32,
+    /// Non-speech needed to end an utterance, in milliseconds.
+    pub tail_margin_ms: u32,
+    /// Consecutive speech frames needed to trigger.
+    pub trigger_frames: usize,
+}
+
+impl Default for VadConfig {
+    /// Julius' defaults for 16kHz input.
+    fn default() -> Self {
+        Self {
+            head_margin_ms: 300,
+            tail_margin_ms: 400,
+            trigger_frames: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Silence,
+    Speech,
+    Ended,
+}
+
+/// [`AudioSource`] passing only the speech of another mono `SP16` source.
+pub struct VadSource<A: AudioSource<Sample = i16>, V: Vad> {
+    source: A,
+    vad: V,
+    state: State,
+    frame: Vec<i16>,
+    /// Samples before the trigger, bounded by the head margin.
+    head: VecDeque<i16>,
+    head_len: usize,
+    tail_len: usize,
+    trigger_frames: usize,
+    speech_frames: usize,
+    silence_len: usize,
+    /// Number of samples taken from the source and classified.
+    position: u64,
+    output: VecDeque<AdinRead<Vec<i16>>>,
+    on_event: Option<Box<dyn FnMut(VadEvent)>>,
+}
+
+impl<A: AudioSource<Sample = i16>, V: Vad> VadSource<A, V> {
+    pub fn new(source: A, mut vad: V, config: VadConfig) -> Result<Self, anyhow::Error> {
+        if source.format() != InputFormat::mono() {
+            return Err(anyhow::anyhow!("VAD requires mono input"));
+        }
+        if vad.frame_len() == 0 {
+            return Err(anyhow::anyhow!("VAD frame length must not be zero"));
+        }
+        vad.reset();
+        let rate = source.sample_rate() as u64;
+        Ok(Self {
+            frame: Vec::with_capacity(vad.frame_len()),
+            head: VecDeque::new(),
+            head_len: (config.head_margin_ms as u64 * rate / 1000) as usize,
+            tail_len: (config.tail_margin_ms as u64 * rate / 1000) as usize,
+            trigger_frames: config.trigger_frames.max(1),
+            speech_frames: 0,
+            silence_len: 0,
+            position: 0,
+            output: VecDeque::new(),
+            on_event: None,
+            state: State::Silence,
+            source,
+            vad,
+        })
+    }
+
+    /// Sets a callback receiving [`VadEvent`]s as they are detected.
+    pub fn with_callback<F: FnMut(VadEvent) + 'static>(mut self, on_event: F) -> Self {
+        self.on_event = Some(Box::new(on_event));
+        self
+    }
+
+    pub fn is_speech(&self) -> bool {
+        self.state == State::Speech
+    }
+
+    fn emit(&mut self, event: VadEvent) {
+        if let Some(on_event) = &mut self.on_event {
+            on_event(event);
+        }
+    }
+
+    fn process_frame(&mut self) {
+        let frame = std::mem::take(&mut self.frame);
+        let speech = self.vad.is_speech(&frame);
+        self.position += frame.len() as u64;
+
+        match self.state {
+            State::Silence => {
+                self.head.extend(&frame);
+                self.speech_frames = if speech { self.speech_frames + 1 } else { 0 };
+                if self.speech_frames >= self.trigger_frames {
+                    let triggered = self.speech_frames * frame.len();
+                    let keep = (self.head_len + triggered).min(self.head.len());
+                    self.head.drain(..self.head.len() - keep);
+                    let sample = self.position - keep as u64;
+                    self.emit(VadEvent::SpeechStart { sample });
+                    self.output
+                        .push_back(AdinRead::Samples(self.head.drain(..).collect()));
+                    self.state = State::Speech;
+                    self.speech_frames = 0;
+                    self.silence_len = 0;
+                } else {
+                    let excess = self
+                        .head
+                        .len()
+                        .saturating_sub(self.head_len + self.speech_frames * frame.len());
+                    self.head.drain(..excess);
+                }
+            }
+            State::Speech => {
+                self.silence_len = if speech {
+                    0
+                } else {
+                    self.silence_len + frame.len()
+                };
+                self.output.push_back(AdinRead::Samples(frame.clone()));
+                if self.silence_len >= self.tail_len {
+                    self.stop_speech();
+                }
+            }
+            State::Ended => {}
+        }
+        self.frame = frame;
+        self.frame.clear();
+    }
+
+    /// Passes an incomplete frame through without classifying it.
+    fn flush_frame(&mut self) {
+        self.position += self.frame.len() as u64;
+        if self.state == State::Speech && !self.frame.is_empty() {
+            self.output.push_back(AdinRead::Samples(self.frame.clone()));
+        }
+        self.frame.clear();
+    }
+
+    fn stop_speech(&mut self) {
+        if self.state == State::Speech {
+            self.emit(VadEvent::SpeechStop {
+                sample: self.position,
+            });
+            self.output.push_back(AdinRead::EndOfSegment);
+            self.state = State::Silence;
+        }
+        self.head.clear();
+        self.speech_frames = 0;
+        self.silence_len = 0;
+    }
+}
+
+impl<A: AudioSource<Sample = i16>, V: Vad> AudioSource for VadSource<A, V> {
+    type Sample = i16;
+    fn format(&self) -> InputFormat {
+        InputFormat::mono()
+    }
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+    fn read(&mut self, max: usize) -> AdinRead<Vec<i16>> {
+        loop {
+            match self.output.pop_front() {
+                Some(AdinRead::Samples(mut samples)) => {
+                    if samples.len() > max {
+                        let rest = samples.split_off(max);
+                        self.output.push_front(AdinRead::Samples(rest));
+                    }
+                    if !samples.is_empty() {
+                        return AdinRead::Samples(samples);
+                    }
+                }
+                Some(other) => return other,
+                None if self.state == State::Ended => return AdinRead::EndOfStream,
+                None => {
+                    let frame_len = self.vad.frame_len();
+                    match self.source.read(frame_len - self.frame.len()) {
+                        // Nothing available yet, let libjulius call again
+                        AdinRead::Samples(samples) if samples.is_empty() => {
+                            return AdinRead::Samples(samples)
+                        }
+                        AdinRead::Samples(samples) => {
+                            for sample in samples {
+                                self.frame.push(sample);
+                                if self.frame.len() == frame_len {
+                                    self.process_frame();
+                                }
+                            }
+                        }
+                        // The noise estimate carries over to the next segment
+                        AdinRead::EndOfSegment => {
+                            self.flush_frame();
+                            self.stop_speech();
+                        }
+                        AdinRead::EndOfStream => {
+                            self.flush_frame();
+                            self.stop_speech();
+                            self.state = State::Ended;
+                        }
+                        AdinRead::Error => return AdinRead::Error,
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    /// Source replaying fixed reads at 16kHz.
+    struct Replay(VecDeque<AdinRead<Vec<i16>>>);
+
+    impl AudioSource for Replay {
+        type Sample = i16;
+        fn format(&self) -> InputFormat {
+            InputFormat::mono()
+        }
+        fn sample_rate(&self) -> u32 {
+            16000
+        }
+        fn read(&mut self, max: usize) -> AdinRead<Vec<i16>> {
+            match self.0.pop_front() {
+                Some(AdinRead::Samples(mut samples)) => {
+                    if samples.len() > max {
+                        self.0.push_front(AdinRead::Samples(samples.split_off(max)));
+                    }
+                    AdinRead::Samples(samples)
+                }
+                Some(other) => other,
+                None => AdinRead::EndOfStream,
+            }
+        }
+    }
+
+    /// Frames of 10ms, speech where any sample is non-zero.
+    fn nonzero_vad() -> FnVad<impl FnMut(&[i16]) -> bool> {
+        FnVad::new(160, |frame: &[i16]| frame.iter().any(|&s| s != 0))
+    }
+
+    fn run<V: Vad>(
+        reads: Vec<AdinRead<Vec<i16>>>,
+        vad: V,
+    ) -> (Vec<AdinRead<Vec<i16>>>, Vec<VadEvent>) {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let e = events.clone();
+        let mut source = VadSource::new(Replay(reads.into()), vad, VadConfig::default())
+            .unwrap()
+            .with_callback(move |event| e.borrow_mut().push(event));
+        let mut output: Vec<AdinRead<Vec<i16>>> = Vec::new();
+        loop {
+            let read = source.read(1000);
+            let end = read == AdinRead::EndOfStream;
+            // Merge consecutive samples to compare whole utterances
+            match (output.last_mut(), read) {
+                (Some(AdinRead::Samples(last)), AdinRead::Samples(samples)) => last.extend(samples),
+                (_, read) => output.push(read),
+            }
+            if end {
+                break;
+            }
+        }
+        let events = events.borrow().clone();
+        (output, events)
+    }
+
+    #[test]
+    fn passes_speech_with_margins() {
+        let mut input = vec![0; 16000];
+        input.extend(vec![1000; 8000]);
+        input.extend(vec![0; 16000]);
+        let (output, events) = run(vec![AdinRead::Samples(input)], nonzero_vad());
+
+        // 300ms head margin, 400ms tail margin
+        let mut expected = vec![0; 4800];
+        expected.extend(vec![1000; 8000]);
+        expected.extend(vec![0; 6400]);
+        assert_eq!(
+            output,
+            [
+                AdinRead::Samples(expected),
+                AdinRead::EndOfSegment,
+                AdinRead::EndOfStream
+            ]
+        );
+        assert_eq!(
+            events,
+            [
+                VadEvent::SpeechStart { sample: 11200 },
+                VadEvent::SpeechStop { sample: 30400 }
+            ]
+        );
+    }
+
+    #[test]
+    fn ends_speech_at_end_of_input() {
+        let reads = vec![
+            AdinRead::Samples(vec![1000; 1600]),
+            AdinRead::EndOfSegment,
+            AdinRead::Samples(vec![1000; 1650]),
+        ];
+        let (output, events) = run(reads, nonzero_vad());
+        assert_eq!(
+            output,
+            [
+                AdinRead::Samples(vec![1000; 1600]),
+                AdinRead::EndOfSegment,
+                // The incomplete frame at the end is passed through
+                AdinRead::Samples(vec![1000; 1650]),
+                AdinRead::EndOfSegment,
+                AdinRead::EndOfStream
+            ]
+        );
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[3], VadEvent::SpeechStop { sample: 3250 });
+    }
+
+    #[test]
+    fn returns_empty_read_when_source_has_no_data() {
+        let reads = vec![
+            AdinRead::Samples(vec![]),
+            AdinRead::Samples(vec![1000; 160]),
+        ];
+        let mut source =
+            VadSource::new(Replay(reads.into()), nonzero_vad(), VadConfig::default()).unwrap();
+        assert_eq!(source.read(1000), AdinRead::Samples(vec![]));
+        assert_eq!(source.read(1000), AdinRead::Samples(vec![1000; 160]));
+    }
+
+    #[test]
+    fn resets_vad_only_at_stream_start() {
+        struct Counting(Rc<RefCell<usize>>);
+        impl Vad for Counting {
+            fn frame_len(&self) -> usize {
+                160
+            }
+            fn is_speech(&mut self, frame: &[i16]) -> bool {
+                frame[0] != 0
+            }
+            fn reset(&mut self) {
+                *self.0.borrow_mut() += 1;
+            }
+        }
+
+        let resets = Rc::new(RefCell::new(0));
+        let reads = vec![
+            AdinRead::Samples(vec![1000; 320]),
+            AdinRead::EndOfSegment,
+            AdinRead::Samples(vec![1000; 320]),
+        ];
+        run(reads, Counting(resets.clone()));
+        assert_eq!(*resets.borrow(), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        struct Stereo;
+        impl AudioSource for Stereo {
+            type Sample = i16;
+            fn format(&self) -> InputFormat {
+                InputFormat::interleaved(2, crate::adin::ChannelMode::Mix)
+            }
+            fn sample_rate(&self) -> u32 {
+                16000
+            }
+            fn read(&mut self, _max: usize) -> AdinRead<Vec<i16>> {
+                AdinRead::EndOfStream
+            }
+        }
+        assert!(VadSource::new(Stereo, nonzero_vad(), VadConfig::default()).is_err());
+        let empty = Replay(VecDeque::new());
+        assert!(
+            VadSource::new(empty, FnVad::new(0, |_: &[i16]| true), VadConfig::default()).is_err()
+        );
+    }
+}
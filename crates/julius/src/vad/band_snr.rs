@@ -0,0 +1,282 @@
+use std::f32::consts::PI;
+
+use super::{Vad, VadMode};
+
+/// Sub-bands used by WebRTC's VAD, in Hz.
+const BANDS: [(f32, f32); 6] = [
+    (80.0, 250.0),
+    (250.0, 500.0),
+    (500.0, 1000.0),
+    (1000.0, 2000.0),
+    (2000.0, 3000.0),
+    (3000.0, 4000.0),
+];
+/// Weights of the bands in the global decision, favoring the formant region.
+const WEIGHTS: [f32; 6] = [6.0, 8.0, 10.0, 12.0, 14.0, 16.0];
+/// Frames used to initialize the noise estimate.
+const INIT_FRAMES: u32 = 10;
+/// Frame energy in dB below which the input is never speech.
+const MIN_ENERGY_DB: f32 = 10.0;
+
+impl VadMode {
+    /// Thresholds of the weighted and the single-band SNR in dB.
+    fn thresholds(self) -> (f32, f32) {
+        match self {
+            Self::Quality => (4.0, 12.0),
+            Self::LowBitrate => (6.0, 14.0),
+            Self::Aggressive => (8.0, 16.0),
+            Self::VeryAggressive => (10.0, 18.0),
+        }
+    }
+}
+
+/// Detector comparing the energy of six frequency bands with their noise floor.
+///
+/// The spectrum of each frame is split into the six bands of WebRTC's VAD, and the energy of
+/// every band is compared with an adaptive noise floor. A frame is speech when the weighted
+/// SNR, or the SNR of any single band, exceeds the threshold of the [`VadMode`].
+///
+/// An extra for builds without the `fvad` feature. It is not a port of libfvad, which scores
+/// the bands with Gaussian mixture models of speech and noise, so its decisions differ from
+/// those of `FvadVad`, especially on stationary noise.
+#[derive(Debug, Clone)]
+pub struct BandSnrVad {
+    mode: VadMode,
+    sample_rate: u32,
+    frame_len: usize,
+    fft_len: usize,
+    window: Vec<f32>,
+    /// FFT bin ranges of the bands below the Nyquist frequency.
+    bins: Vec<(usize, usize)>,
+    noise: [f32; 6],
+    frames: u32,
+}
+
+impl BandSnrVad {
+    /// `frame_ms` is the frame length, 10, 20 or 30ms like libfvad.
+    pub fn new(sample_rate: u32, frame_ms: u32, mode: VadMode) -> Result<Self, anyhow::Error> {
+        if !matches!(frame_ms, 10 | 20 | 30) {
+            return Err(anyhow::anyhow!("Frame length must be 10, 20 or 30ms"));
+        }
+        if sample_rate < 8000 {
+            return Err(anyhow::anyhow!("Sampling rate must be 8kHz or higher"));
+        }
+        let frame_len = (sample_rate * frame_ms / 1000) as usize;
+        let fft_len = frame_len.next_power_of_two();
+        let window = (0..frame_len)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / (frame_len - 1) as f32).cos())
+            .collect();
+        let hz_per_bin = sample_rate as f32 / fft_len as f32;
+        let bins = BANDS
+            .iter()
+            .map(|&(lo, hi)| {
+                let lo = (lo / hz_per_bin).ceil() as usize;
+                let hi = ((hi / hz_per_bin).ceil() as usize).min(fft_len / 2);
+                (lo, hi.max(lo + 1))
+            })
+            .collect();
+        Ok(Self {
+            mode,
+            sample_rate,
+            frame_len,
+            fft_len,
+            window,
+            bins,
+            noise: [0.0; 6],
+            frames: 0,
+        })
+    }
+
+    pub fn mode(&self) -> VadMode {
+        self.mode
+    }
+    pub fn set_mode(&mut self, mode: VadMode) {
+        self.mode = mode;
+    }
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Energies of the bands in dB.
+    fn band_energies(&self, frame: &[i16]) -> [f32; 6] {
+        let mut re = vec![0.0f32; self.fft_len];
+        let mut im = vec![0.0f32; self.fft_len];
+        for (i, (&s, &w)) in frame.iter().zip(&self.window).enumerate() {
+            re[i] = s as f32 * w;
+        }
+        fft(&mut re, &mut im);
+
+        let mut energies = [0.0; 6];
+        for (energy, &(lo, hi)) in energies.iter_mut().zip(&self.bins) {
+            let power: f32 = (lo..hi).map(|k| re[k] * re[k] + im[k] * im[k]).sum();
+            *energy = 10.0 * (power / self.fft_len as f32 + 1.0).log10();
+        }
+        energies
+    }
+}
+
+impl Vad for BandSnrVad {
+    fn frame_len(&self) -> usize {
+        self.frame_len
+    }
+    fn is_speech(&mut self, frame: &[i16]) -> bool {
+        let energies = self.band_energies(frame);
+
+        if self.frames < INIT_FRAMES {
+            // Assume the beginning of the input is noise
+            let n = self.frames as f32;
+            for (noise, &e) in self.noise.iter_mut().zip(&energies) {
+                *noise = (*noise * n + e) / (n + 1.0);
+            }
+            self.frames += 1;
+            return false;
+        }
+
+        let (global_thres, local_thres) = self.mode.thresholds();
+        let snr: Vec<f32> = energies
+            .iter()
+            .zip(&self.noise)
+            .map(|(e, n)| (e - n).max(0.0))
+            .collect();
+        let weighted =
+            snr.iter().zip(WEIGHTS).map(|(s, w)| s * w).sum::<f32>() / WEIGHTS.iter().sum::<f32>();
+        let peak = snr.iter().copied().fold(0.0, f32::max);
+        let loudest = energies.iter().copied().fold(0.0, f32::max);
+        let speech = loudest > MIN_ENERGY_DB && (weighted > global_thres || peak > local_thres);
+
+        // Follow decreases quickly and increases slowly, only outside speech
+        for (noise, &e) in self.noise.iter_mut().zip(&energies) {
+            if e < *noise {
+                *noise += 0.2 * (e - *noise);
+            } else if !speech {
+                *noise += 0.02 * (e - *noise);
+            }
+        }
+        speech
+    }
+    fn reset(&mut self) {
+        self.noise = [0.0; 6];
+        self.frames = 0;
+    }
+}
+
+/// In-place radix-2 FFT. The length must be a power of two.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let tr = re[b] * cos - im[b] * sin;
+                let ti = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - tr;
+                im[b] = im[a] - ti;
+                re[a] += tr;
+                im[a] += ti;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(freq: f32, amplitude: f32, len: usize, offset: usize) -> Vec<i16> {
+        (offset..offset + len)
+            .map(|i| (amplitude * (2.0 * PI * freq * i as f32 / 16000.0).sin()) as i16)
+            .collect()
+    }
+
+    /// Deterministic low-level noise.
+    fn noise(len: usize, seed: &mut u32) -> Vec<i16> {
+        (0..len)
+            .map(|_| {
+                *seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                ((*seed >> 16) % 200) as i16 - 100
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fft_matches_dft() {
+        let input: Vec<f32> = (0..16).map(|i| ((i * 7) % 5) as f32 - 2.0).collect();
+        let mut re = input.clone();
+        let mut im = vec![0.0; 16];
+        fft(&mut re, &mut im);
+        for k in 0..16 {
+            let (mut dr, mut di) = (0.0, 0.0);
+            for (n, x) in input.iter().enumerate() {
+                let angle = -2.0 * PI * (k * n) as f32 / 16.0;
+                dr += x * angle.cos();
+                di += x * angle.sin();
+            }
+            assert!((re[k] - dr).abs() < 1e-3, "re[{}]", k);
+            assert!((im[k] - di).abs() < 1e-3, "im[{}]", k);
+        }
+    }
+
+    #[test]
+    fn fft_of_impulse_is_flat() {
+        let mut re = vec![0.0; 8];
+        let mut im = vec![0.0; 8];
+        re[0] = 1.0;
+        fft(&mut re, &mut im);
+        assert!(re.iter().all(|&v| (v - 1.0).abs() < 1e-6));
+        assert!(im.iter().all(|&v| v.abs() < 1e-6));
+    }
+
+    #[test]
+    fn detects_tone_over_noise() {
+        let mut vad = BandSnrVad::new(16000, 10, VadMode::Quality).unwrap();
+        assert_eq!(vad.frame_len(), 160);
+        let mut seed = 1;
+        for _ in 0..INIT_FRAMES + 20 {
+            assert!(!vad.is_speech(&noise(160, &mut seed)));
+        }
+        for i in 0..10 {
+            let frame: Vec<i16> = tone(700.0, 5000.0, 160, i * 160)
+                .iter()
+                .zip(noise(160, &mut seed))
+                .map(|(t, n)| t + n)
+                .collect();
+            assert!(vad.is_speech(&frame), "frame {}", i);
+        }
+        assert!(!vad.is_speech(&noise(160, &mut seed)));
+    }
+
+    #[test]
+    fn treats_start_of_input_as_noise() {
+        let mut vad = BandSnrVad::new(16000, 10, VadMode::Quality).unwrap();
+        for i in 0..INIT_FRAMES as usize {
+            assert!(!vad.is_speech(&tone(700.0, 5000.0, 160, i * 160)));
+        }
+        vad.reset();
+        assert!(!vad.is_speech(&tone(700.0, 5000.0, 160, 0)));
+    }
+
+    #[test]
+    fn validates_parameters() {
+        assert!(BandSnrVad::new(16000, 15, VadMode::Quality).is_err());
+        assert!(BandSnrVad::new(4000, 10, VadMode::Quality).is_err());
+        let vad = BandSnrVad::new(8000, 30, VadMode::VeryAggressive).unwrap();
+        assert_eq!(vad.frame_len(), 240);
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::VecDeque;
+
+use super::Vad;
+
+/// Level and zero-cross detector, the same method as libjulius' `adin_cut`.
+///
+/// A zero cross is counted when the waveform swings across `±level_thres`. The input is
+/// speech while the rate of zero crosses over the last `window_ms` reaches `zero_cross_num`
+/// per second.
+#[derive(Debug, Clone)]
+pub struct EnergyVad {
+    /// Level threshold, equivalent to `-lv`.
+    pub level_thres: i16,
+    /// Zero crosses per second, equivalent to `-zc`.
+    pub zero_cross_num: u32,
+    frame_len: usize,
+    window_frames: usize,
+    sample_rate: u32,
+    /// Sign of the last sample beyond the threshold.
+    sign: i8,
+    counts: VecDeque<u32>,
+}
+
+impl EnergyVad {
+    /// Frame length in milliseconds.
+    pub const FRAME_MS: u32 = 10;
+
+    /// Creates a detector with Julius' defaults (`-lv 2000 -zc 60`, 300ms window).
+    pub fn new(sample_rate: u32) -> Self {
+        Self::with_params(sample_rate, 2000, 60, 300)
+    }
+
+    pub fn with_params(
+        sample_rate: u32,
+        level_thres: i16,
+        zero_cross_num: u32,
+        window_ms: u32,
+    ) -> Self {
+        Self {
+            level_thres,
+            zero_cross_num,
+            frame_len: (sample_rate * Self::FRAME_MS / 1000).max(1) as usize,
+            window_frames: (window_ms / Self::FRAME_MS).max(1) as usize,
+            sample_rate,
+            sign: 0,
+            counts: VecDeque::new(),
+        }
+    }
+}
+
+impl Vad for EnergyVad {
+    fn frame_len(&self) -> usize {
+        self.frame_len
+    }
+    fn is_speech(&mut self, frame: &[i16]) -> bool {
+        let thres = self.level_thres.max(0);
+        let mut count = 0;
+        for &s in frame {
+            let sign = if s > thres {
+                1
+            } else if s < -thres {
+                -1
+            } else {
+                continue;
+            };
+            if sign != self.sign {
+                if self.sign != 0 {
+                    count += 1;
+                }
+                self.sign = sign;
+            }
+        }
+
+        self.counts.push_back(count);
+        if self.counts.len() > self.window_frames {
+            self.counts.pop_front();
+        }
+        let total: u64 = self.counts.iter().map(|&c| c as u64).sum();
+        let window_samples = (self.window_frames * self.frame_len) as u64;
+        // Compare rates without rounding: total / window >= zero_cross_num / sample_rate
+        total * self.sample_rate as u64 >= self.zero_cross_num as u64 * window_samples
+    }
+    fn reset(&mut self) {
+        self.sign = 0;
+        self.counts.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Square wave of `freq` Hz at 16kHz.
+    fn square(freq: usize, amplitude: i16, len: usize) -> Vec<i16> {
+        let half = 16000 / freq / 2;
+        (0..len)
+            .map(|i| {
+                if (i / half).is_multiple_of(2) {
+                    amplitude
+                } else {
+                    -amplitude
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_zero_crosses_above_level() {
+        let mut vad = EnergyVad::new(16000);
+        assert_eq!(vad.frame_len(), 160);
+        assert!(!vad.is_speech(&[0; 160]));
+        assert!(!vad.is_speech(&square(1000, 1000, 160)));
+        assert!(vad.is_speech(&square(1000, 5000, 160)));
+    }
+
+    #[test]
+    fn follows_rate_over_window() {
+        // 60 crosses per second over 300ms is 18 crosses. Alternating frames of constant level
+        // cross once per frame after the first.
+        let mut vad = EnergyVad::new(16000);
+        let frame = |i: usize| [if i.is_multiple_of(2) { 5000 } else { -5000 }; 160];
+        for i in 0..18 {
+            assert!(!vad.is_speech(&frame(i)), "frame {}", i);
+        }
+        assert!(vad.is_speech(&frame(18)));
+        // Crosses fall out of the window
+        for _ in 0..12 {
+            vad.is_speech(&[0; 160]);
+        }
+        assert!(!vad.is_speech(&[0; 160]));
+    }
+
+    #[test]
+    fn reset_forgets_window() {
+        let mut vad = EnergyVad::with_params(16000, 2000, 60, 300);
+        assert!(vad.is_speech(&square(1000, 5000, 160)));
+        vad.reset();
+        assert!(!vad.is_speech(&[0; 160]));
+    }
+}
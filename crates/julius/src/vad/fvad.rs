@@ -0,0 +1,111 @@
+use std::ptr::NonNull;
+
+use super::{Vad, VadMode};
+
+/// WebRTC's VAD by libfvad, the detector of Julius' `-fvad`.
+#[derive(Debug)]
+pub struct FvadVad {
+    inst: NonNull<libjulius_sys::Fvad>,
+    mode: VadMode,
+    sample_rate: u32,
+    frame_len: usize,
+}
+
+// The instance is owned and only used through `&mut self`.
+unsafe impl Send for FvadVad {}
+
+impl FvadVad {
+    /// `sample_rate` is 8, 16, 32 or 48kHz and `frame_ms` is 10, 20 or 30ms, as libfvad
+    /// accepts.
+    pub fn new(sample_rate: u32, frame_ms: u32, mode: VadMode) -> Result<Self, anyhow::Error> {
+        if !matches!(frame_ms, 10 | 20 | 30) {
+            return Err(anyhow::anyhow!("Frame length must be 10, 20 or 30ms"));
+        }
+        if !matches!(sample_rate, 8000 | 16000 | 32000 | 48000) {
+            return Err(anyhow::anyhow!(
+                "Sampling rate must be 8, 16, 32 or 48kHz for libfvad"
+            ));
+        }
+        let inst = NonNull::new(unsafe { libjulius_sys::fvad_new() })
+            .ok_or_else(|| anyhow::anyhow!("Failed to create libfvad instance"))?;
+        let mut vad = Self {
+            inst,
+            mode,
+            sample_rate,
+            frame_len: (sample_rate * frame_ms / 1000) as usize,
+        };
+        vad.configure()?;
+        Ok(vad)
+    }
+
+    /// Applies the mode and the sampling rate to the instance.
+    fn configure(&mut self) -> Result<(), anyhow::Error> {
+        if unsafe { libjulius_sys::fvad_set_sample_rate(self.inst.as_ptr(), self.sample_rate as _) }
+            != 0
+        {
+            return Err(anyhow::anyhow!("libfvad rejected the sampling rate"));
+        }
+        if unsafe { libjulius_sys::fvad_set_mode(self.inst.as_ptr(), self.mode as _) } != 0 {
+            return Err(anyhow::anyhow!("libfvad rejected the mode"));
+        }
+        Ok(())
+    }
+
+    pub fn mode(&self) -> VadMode {
+        self.mode
+    }
+    pub fn set_mode(&mut self, mode: VadMode) {
+        self.mode = mode;
+        // Every mode is valid
+        unsafe { libjulius_sys::fvad_set_mode(self.inst.as_ptr(), mode as _) };
+    }
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
+
+impl Vad for FvadVad {
+    fn frame_len(&self) -> usize {
+        self.frame_len
+    }
+    /// Frames of another length are not speech.
+    fn is_speech(&mut self, frame: &[i16]) -> bool {
+        if frame.len() != self.frame_len {
+            return false;
+        }
+        unsafe { libjulius_sys::fvad_process(self.inst.as_ptr(), frame.as_ptr(), frame.len()) == 1 }
+    }
+    fn reset(&mut self) {
+        unsafe { libjulius_sys::fvad_reset(self.inst.as_ptr()) };
+        // fvad_reset restores the default mode and sampling rate, which were valid before
+        let _ = self.configure();
+    }
+}
+
+impl Drop for FvadVad {
+    fn drop(&mut self) {
+        unsafe { libjulius_sys::fvad_free(self.inst.as_ptr()) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_unsupported_parameters() {
+        assert!(FvadVad::new(16000, 15, VadMode::Quality).is_err());
+        assert!(FvadVad::new(22050, 10, VadMode::Quality).is_err());
+    }
+
+    #[test]
+    fn keeps_settings_over_reset() {
+        let mut vad = FvadVad::new(8000, 30, VadMode::Aggressive).unwrap();
+        assert_eq!(vad.frame_len(), 240);
+        vad.reset();
+        assert_eq!(vad.mode(), VadMode::Aggressive);
+        assert_eq!(vad.sample_rate(), 8000);
+        assert!(!vad.is_speech(&[0; 240]));
+        assert!(!vad.is_speech(&[0; 160]));
+    }
+}
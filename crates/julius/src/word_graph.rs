@@ -0,0 +1,443 @@
+use std::{collections::HashMap, io::Write, marker::PhantomData};
+
+use libjulius_sys::WORD_ID;
+
+use crate::recog_process::RecogProcess;
+
+/// Word graph (lattice) of a recognition result.
+///
+/// Available from [`CallbackType::ResultGraph`](crate::CallbackType::ResultGraph) when
+/// lattice output (`-lattice`) is enabled.
+#[derive(Debug, Clone, Copy)]
+pub struct WordGraph<'a> {
+    root: *mut libjulius_sys::WordGraph,
+    _marker: PhantomData<&'a libjulius_sys::WordGraph>,
+}
+
+impl<'a> WordGraph<'a> {
+    pub(crate) fn new(root: *mut libjulius_sys::WordGraph) -> Option<Self> {
+        if root.is_null() {
+            None
+        } else {
+            Some(Self {
+                root,
+                _marker: PhantomData,
+            })
+        }
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = WordGraphNode<'a>> {
+        let mut current = self.root;
+        std::iter::from_fn(move || {
+            let node = unsafe { current.as_ref()? };
+            current = node.next;
+            Some(WordGraphNode(node))
+        })
+    }
+
+    /// Returns the node whose id is `id`.
+    pub fn node(&self, id: i32) -> Option<WordGraphNode<'a>> {
+        self.nodes().find(|n| n.id() == id)
+    }
+
+    /// Nodes without left context, i.e. the beginning of the utterance.
+    pub fn initial_nodes(&self) -> impl Iterator<Item = WordGraphNode<'a>> {
+        self.nodes().filter(|n| n.0.leftwordnum == 0)
+    }
+
+    /// Nodes without right context, i.e. the end of the utterance.
+    pub fn final_nodes(&self) -> impl Iterator<Item = WordGraphNode<'a>> {
+        self.nodes().filter(|n| n.0.rightwordnum == 0)
+    }
+
+    /// Writes the graph in HTK Standard Lattice Format.
+    ///
+    /// Words are placed on SLF nodes, with `!NULL` nodes added at the start and the end.
+    /// Each link carries the acoustic score of the word it enters (`a`) and
+    /// the language score of the transition (`l`).
+    /// The scores are log10 likelihoods as in Julius, declared by `base=10.0` in the header,
+    /// since HTK assumes natural logarithms otherwise.
+    /// `frame_period` is the frame shift in seconds, e.g. [`Recog::frame_period`](crate::Recog::frame_period).
+    pub fn write_slf<W: Write>(
+        &self,
+        writer: &mut W,
+        process: &RecogProcess,
+        frame_period: f64,
+    ) -> Result<(), anyhow::Error> {
+        self.write_slf_with(writer, |w| word_name(process, w), frame_period)
+    }
+
+    fn write_slf_with<W: Write>(
+        &self,
+        writer: &mut W,
+        word_name: impl Fn(WORD_ID) -> String,
+        frame_period: f64,
+    ) -> Result<(), anyhow::Error> {
+        let nodes: Vec<_> = self.nodes().collect();
+        let index = self.node_index();
+        let end = nodes.len() + 1;
+        let end_time = nodes.iter().map(|n| n.right_time() + 1).max().unwrap_or(0);
+
+        let mut links = Vec::new();
+        for node in self.initial_nodes() {
+            links.push((0, index[&node.ptr()], node.am_score(), node.lm_score()));
+        }
+        for node in &nodes {
+            for arc in node.right_arcs() {
+                links.push((
+                    index[&node.ptr()],
+                    index[&arc.node.ptr()],
+                    arc.node.am_score(),
+                    arc.lm_score.unwrap_or_else(|| arc.node.lm_score()),
+                ));
+            }
+        }
+        for node in self.final_nodes() {
+            links.push((index[&node.ptr()], end, 0.0, 0.0));
+        }
+
+        writeln!(writer, "VERSION=1.0")?;
+        writeln!(writer, "base=10.0")?;
+        writeln!(writer, "N={} L={}", nodes.len() + 2, links.len())?;
+        writeln!(writer, "I=0 t=0.00 W=!NULL")?;
+        for node in &nodes {
+            writeln!(
+                writer,
+                "I={} t={:.2} W={}",
+                index[&node.ptr()],
+                (node.right_time() + 1) as f64 * frame_period,
+                escape_slf(&word_name(node.word()))
+            )?;
+        }
+        writeln!(
+            writer,
+            "I={} t={:.2} W=!NULL",
+            end,
+            end_time as f64 * frame_period
+        )?;
+        for (j, (start, end, a, l)) in links.iter().enumerate() {
+            writeln!(
+                writer,
+                "J={} S={} E={} a={:.6} l={:.6}",
+                j, start, end, a, l
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes the graph in Graphviz DOT format.
+    pub fn write_dot<W: Write>(
+        &self,
+        writer: &mut W,
+        process: &RecogProcess,
+    ) -> Result<(), anyhow::Error> {
+        self.write_dot_with(writer, |w| word_name(process, w))
+    }
+
+    fn write_dot_with<W: Write>(
+        &self,
+        writer: &mut W,
+        word_name: impl Fn(WORD_ID) -> String,
+    ) -> Result<(), anyhow::Error> {
+        let index = self.node_index();
+        writeln!(writer, "digraph wordgraph {{")?;
+        writeln!(writer, "  rankdir=LR;")?;
+        for node in self.nodes() {
+            writeln!(
+                writer,
+                "  n{} [label=\"{}\\n[{}..{}]\\ncm={:.3}\"];",
+                index[&node.ptr()],
+                escape_dot(&word_name(node.word())),
+                node.left_time(),
+                node.right_time(),
+                node.graph_cm()
+            )?;
+        }
+        for node in self.nodes() {
+            for arc in node.right_arcs() {
+                write!(
+                    writer,
+                    "  n{} -> n{}",
+                    index[&node.ptr()],
+                    index[&arc.node.ptr()]
+                )?;
+                match arc.lm_score {
+                    Some(l) => writeln!(writer, " [label=\"{:.3}\"];", l)?,
+                    None => writeln!(writer, ";")?,
+                }
+            }
+        }
+        writeln!(writer, "}}")?;
+        Ok(())
+    }
+
+    /// Maps each node to its position in [`WordGraph::nodes`], starting from 1.
+    fn node_index(&self) -> HashMap<*const libjulius_sys::WordGraph, usize> {
+        self.nodes()
+            .enumerate()
+            .map(|(i, n)| (n.ptr(), i + 1))
+            .collect()
+    }
+}
+
+fn word_name(process: &RecogProcess, w: WORD_ID) -> String {
+    process
+        .word_name(w)
+        .and_then(Result::ok)
+        .unwrap_or_else(|| w.to_string())
+}
+
+fn escape_slf(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, ' ' | '"' | '\'' | '\\' | '=') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Word hypothesis in a [`WordGraph`].
+#[derive(Debug, Clone, Copy)]
+pub struct WordGraphNode<'a>(&'a libjulius_sys::WordGraph);
+
+impl<'a> WordGraphNode<'a> {
+    pub fn id(&self) -> i32 {
+        self.0.id
+    }
+    /// Word ID in the dictionary.
+    pub fn word(&self) -> WORD_ID {
+        self.0.wid
+    }
+    /// First frame of the word.
+    pub fn left_time(&self) -> i32 {
+        self.0.lefttime
+    }
+    /// Last frame of the word.
+    pub fn right_time(&self) -> i32 {
+        self.0.righttime
+    }
+    /// Partial sentence score when the next word was expanded at the head.
+    pub fn fscore_head(&self) -> f32 {
+        self.0.fscore_head
+    }
+    pub fn fscore_tail(&self) -> f32 {
+        self.0.fscore_tail
+    }
+    /// Accumulated Viterbi score at the head state of the word.
+    pub fn gscore_head(&self) -> f32 {
+        self.0.gscore_head
+    }
+    pub fn gscore_tail(&self) -> f32 {
+        self.0.gscore_tail
+    }
+    /// Language score of the word.
+    pub fn lm_score(&self) -> f32 {
+        self.0.lscore_tmp
+    }
+    /// Average acoustic score per frame.
+    pub fn am_avg(&self) -> f32 {
+        self.0.amavg
+    }
+    /// Acoustic score of the whole word.
+    pub fn am_score(&self) -> f32 {
+        self.0.amavg * (self.0.righttime - self.0.lefttime + 1) as f32
+    }
+    /// Forward score for posterior computation.
+    pub fn forward_score(&self) -> f32 {
+        self.0.forward_score
+    }
+    /// Backward score for posterior computation.
+    pub fn backward_score(&self) -> f32 {
+        self.0.backward_score
+    }
+    /// Confidence score obtained while search.
+    pub fn cmscore(&self) -> f32 {
+        self.0.cmscore
+    }
+    /// Confidence score (posterior) computed on the graph.
+    pub fn graph_cm(&self) -> f32 {
+        self.0.graph_cm
+    }
+
+    /// Arcs to the preceding words.
+    pub fn left_arcs(&self) -> impl Iterator<Item = WordGraphArc<'a>> {
+        Self::arcs(self.0.leftword, self.0.left_lscore, self.0.leftwordnum)
+    }
+    /// Arcs to the following words.
+    pub fn right_arcs(&self) -> impl Iterator<Item = WordGraphArc<'a>> {
+        Self::arcs(self.0.rightword, self.0.right_lscore, self.0.rightwordnum)
+    }
+
+    fn ptr(&self) -> *const libjulius_sys::WordGraph {
+        self.0
+    }
+
+    fn arcs(
+        words: *mut *mut libjulius_sys::WordGraph,
+        lscores: *mut f32,
+        num: i32,
+    ) -> impl Iterator<Item = WordGraphArc<'a>> {
+        (0..num.max(0) as usize).map(move |i| unsafe {
+            WordGraphArc {
+                node: WordGraphNode(&**words.add(i)),
+                lm_score: if lscores.is_null() {
+                    None
+                } else {
+                    Some(*lscores.add(i))
+                },
+            }
+        })
+    }
+}
+
+impl<'a> PartialEq for WordGraphNode<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+impl<'a> Eq for WordGraphNode<'a> {}
+
+/// Connection between two nodes of a [`WordGraph`].
+#[derive(Debug, Clone, Copy)]
+pub struct WordGraphArc<'a> {
+    pub node: WordGraphNode<'a>,
+    /// Language score of the transition.
+    pub lm_score: Option<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::null_mut;
+
+    use super::*;
+
+    const NAMES: [&str; 3] = ["a", "b c", "d\""];
+
+    /// Nodes and arc arrays of a hand-built graph: `a` followed by `b c` or `d"`.
+    struct Graph {
+        nodes: Vec<libjulius_sys::WordGraph>,
+        _arcs: Vec<Vec<*mut libjulius_sys::WordGraph>>,
+        _lscores: Vec<f32>,
+    }
+
+    impl Graph {
+        fn new() -> Self {
+            let node = |id: i32, (left, right): (i32, i32), amavg, lscore, cm| {
+                let mut node: libjulius_sys::WordGraph = unsafe { std::mem::zeroed() };
+                node.id = id;
+                node.wid = id as WORD_ID;
+                node.lefttime = left;
+                node.righttime = right;
+                node.amavg = amavg;
+                node.lscore_tmp = lscore;
+                node.graph_cm = cm;
+                node
+            };
+            let mut nodes = vec![
+                node(0, (0, 9), -2.0, -1.0, 0.9),
+                node(1, (10, 19), -3.0, -0.5, 0.6),
+                node(2, (10, 24), -1.5, -0.25, 0.4),
+            ];
+            let p: Vec<_> = (0..3)
+                .map(|i| unsafe { nodes.as_mut_ptr().add(i) })
+                .collect();
+            let mut arcs = vec![vec![p[1], p[2]], vec![p[0]], vec![p[0]]];
+            let mut lscores = vec![-0.75, -0.125];
+
+            nodes[0].rightword = arcs[0].as_mut_ptr();
+            nodes[0].right_lscore = lscores.as_mut_ptr();
+            nodes[0].rightwordnum = 2;
+            nodes[0].next = p[1];
+            for i in 1..3 {
+                nodes[i].leftword = arcs[i].as_mut_ptr();
+                nodes[i].left_lscore = null_mut();
+                nodes[i].leftwordnum = 1;
+            }
+            nodes[1].next = p[2];
+            Self {
+                nodes,
+                _arcs: arcs,
+                _lscores: lscores,
+            }
+        }
+
+        fn graph(&mut self) -> WordGraph<'_> {
+            WordGraph::new(self.nodes.as_mut_ptr()).unwrap()
+        }
+    }
+
+    fn name(w: WORD_ID) -> String {
+        NAMES[w as usize].to_string()
+    }
+
+    #[test]
+    fn traverses_nodes_and_arcs() {
+        let mut graph = Graph::new();
+        let graph = graph.graph();
+        assert_eq!(graph.nodes().count(), 3);
+        let initial: Vec<_> = graph.initial_nodes().map(|n| n.id()).collect();
+        assert_eq!(initial, [0]);
+        let last: Vec<_> = graph.final_nodes().map(|n| n.id()).collect();
+        assert_eq!(last, [1, 2]);
+
+        let first = graph.node(0).unwrap();
+        let arcs: Vec<_> = first
+            .right_arcs()
+            .map(|arc| (arc.node.id(), arc.lm_score))
+            .collect();
+        assert_eq!(arcs, [(1, Some(-0.75)), (2, Some(-0.125))]);
+        let left: Vec<_> = graph.node(2).unwrap().left_arcs().collect();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].node, first);
+        assert_eq!(left[0].lm_score, None);
+        assert_eq!(graph.node(2).unwrap().am_score(), -22.5);
+    }
+
+    #[test]
+    fn writes_slf() {
+        let mut graph = Graph::new();
+        let mut slf = Vec::new();
+        graph.graph().write_slf_with(&mut slf, name, 0.01).unwrap();
+        assert_eq!(
+            String::from_utf8(slf).unwrap(),
+            "VERSION=1.0\n\
+             base=10.0\n\
+             N=5 L=5\n\
+             I=0 t=0.00 W=!NULL\n\
+             I=1 t=0.10 W=a\n\
+             I=2 t=0.20 W=b\\ c\n\
+             I=3 t=0.25 W=d\\\"\n\
+             I=4 t=0.25 W=!NULL\n\
+             J=0 S=0 E=1 a=-20.000000 l=-1.000000\n\
+             J=1 S=1 E=2 a=-30.000000 l=-0.750000\n\
+             J=2 S=1 E=3 a=-22.500000 l=-0.125000\n\
+             J=3 S=2 E=4 a=0.000000 l=0.000000\n\
+             J=4 S=3 E=4 a=0.000000 l=0.000000\n"
+        );
+    }
+
+    #[test]
+    fn writes_dot() {
+        let mut graph = Graph::new();
+        let mut dot = Vec::new();
+        graph.graph().write_dot_with(&mut dot, name).unwrap();
+        assert_eq!(
+            String::from_utf8(dot).unwrap(),
+            "digraph wordgraph {\n  \
+             rankdir=LR;\n  \
+             n1 [label=\"a\\n[0..9]\\ncm=0.900\"];\n  \
+             n2 [label=\"b c\\n[10..19]\\ncm=0.600\"];\n  \
+             n3 [label=\"d\\\"\\n[10..24]\\ncm=0.400\"];\n  \
+             n1 -> n2 [label=\"-0.750\"];\n  \
+             n1 -> n3 [label=\"-0.125\"];\n\
+             }\n"
+        );
+    }
+}
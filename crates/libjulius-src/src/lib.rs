@@ -0,0 +1,167 @@
+//! Source of Julius 4.6 for building `libjulius-sys` without network access.
+//!
+//! `julius-4.6` is the release tarball with `libjulius-sys/source.patch` applied, created by
+//! `vendor.sh`.
+
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+/// Version of the vendored source.
+pub const VERSION: &str = "4.6";
+
+/// Returns the source directory, or `None` if the source has not been vendored.
+pub fn source_dir() -> Option<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("julius-4.6");
+    dir.join("libjulius").is_dir().then_some(dir)
+}
+
+/// Hunk of a unified diff, located by its content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Hunk {
+    /// Path of the patched file relative to the source directory.
+    path: PathBuf,
+    /// The `@@` line, for error messages.
+    header: String,
+    old: String,
+    new: String,
+}
+
+/// Splits the unified diff `patch` into hunks, stripping the first component of the paths as
+/// `patch -p1` does.
+fn parse_patch(patch: &str) -> Result<Vec<Hunk>, Box<dyn Error>> {
+    let mut target: Option<PathBuf> = None;
+    let mut hunks: Vec<(Hunk, Vec<&str>, Vec<&str>)> = vec![];
+    for line in patch.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            let path = path.split_once('/').map_or(path, |(_, path)| path);
+            target = Some(PathBuf::from(path.trim_end()));
+        } else if line.starts_with("--- ") || line.starts_with("diff ") {
+            continue;
+        } else if line.starts_with("@@") {
+            let path = target.clone().ok_or("Hunk without a file")?;
+            let hunk = Hunk {
+                path,
+                header: line.to_string(),
+                old: String::new(),
+                new: String::new(),
+            };
+            hunks.push((hunk, vec![], vec![]));
+        } else if let Some((_, old, new)) = hunks.last_mut() {
+            match line.split_at(line.len().min(1)) {
+                ("-", rest) => old.push(rest),
+                ("+", rest) => new.push(rest),
+                (" ", rest) | ("", rest) => {
+                    old.push(rest);
+                    new.push(rest);
+                }
+                // e.g. "\ No newline at end of file"
+                _ => {}
+            }
+        }
+    }
+    Ok(hunks
+        .into_iter()
+        .map(|(hunk, old, new)| Hunk {
+            old: old.join("\n") + "\n",
+            new: new.join("\n") + "\n",
+            ..hunk
+        })
+        .collect())
+}
+
+/// Applies the unified diff `patch` to the source in `dir` unless it already contains it,
+/// e.g. a vendored copy.
+///
+/// Hunks are located by their content rather than their line numbers, so a hunk applies at
+/// any offset, but without fuzz: its context must match exactly.
+pub fn apply_patch(dir: &Path, patch: &str) -> Result<(), Box<dyn Error>> {
+    for hunk in parse_patch(patch)? {
+        let path = dir.join(&hunk.path);
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if text.contains(&hunk.new) && !text.contains(&hunk.old) {
+            continue;
+        }
+        if !text.contains(&hunk.old) {
+            return Err(format!("Hunk {} does not match {}", hunk.header, path.display()).into());
+        }
+        std::fs::write(&path, text.replacen(&hunk.old, &hunk.new, 1))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "\
+diff --git a/src/a.c b/src/a.c
+--- a/src/a.c
++++ b/src/a.c
+@@ -2,3 +2,4 @@ int f()
+ {
+-  return 0;
++  g();
++  return 1;
+ }
+";
+
+    /// Empty directory under the temporary directory.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("libjulius-src-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn parses_hunks() {
+        let hunks = parse_patch(PATCH).unwrap();
+        assert_eq!(
+            hunks,
+            [Hunk {
+                path: PathBuf::from("src/a.c"),
+                header: "@@ -2,3 +2,4 @@ int f()".to_string(),
+                old: "{\n  return 0;\n}\n".to_string(),
+                new: "{\n  g();\n  return 1;\n}\n".to_string(),
+            }]
+        );
+        assert!(parse_patch("@@ -1 +1 @@\n-a\n+b\n").is_err());
+    }
+
+    #[test]
+    fn applies_at_any_offset_once() {
+        let dir = temp_dir("offset");
+        let file = dir.join("src/a.c");
+        // Lines added above the hunk move it from line 2 to line 5
+        std::fs::write(&file, "/* a */\n\n\nint f()\n{\n  return 0;\n}\n").unwrap();
+        apply_patch(&dir, PATCH).unwrap();
+        let patched = "/* a */\n\n\nint f()\n{\n  g();\n  return 1;\n}\n";
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), patched);
+
+        // Already patched, e.g. the vendored source
+        apply_patch(&dir, PATCH).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), patched);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn fails_without_exact_context() {
+        let dir = temp_dir("fuzz");
+        let file = dir.join("src/a.c");
+        std::fs::write(&file, "int f()\n{\n  return  0;\n}\n").unwrap();
+        let error = apply_patch(&dir, PATCH).unwrap_err().to_string();
+        assert!(error.contains("@@ -2,3 +2,4 @@"), "{}", error);
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "int f()\n{\n  return  0;\n}\n"
+        );
+
+        std::fs::remove_file(&file).unwrap();
+        assert!(apply_patch(&dir, PATCH).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
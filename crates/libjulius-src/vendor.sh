@@ -0,0 +1,16 @@
+#!/bin/sh
+# Vendors the Julius 4.6 source into julius-4.6 with the patches of libjulius-sys applied.
+# Usage: vendor.sh [path/to/v4.6.tar.gz]
+set -eu
+cd "$(dirname "$0")"
+
+tarball=${1:-}
+if [ -z "$tarball" ]; then
+    tarball=$(mktemp)
+    trap 'rm -f "$tarball"' EXIT
+    curl -fsSL -o "$tarball" https://github.com/julius-speech/julius/archive/refs/tags/v4.6.tar.gz
+fi
+
+rm -rf julius-4.6
+tar xzf "$tarball"
+patch -d julius-4.6 -p1 < ../libjulius-sys/source.patch
@@ -0,0 +1,16 @@
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    println!("cargo:rerun-if-changed=layout.c");
+
+    // Reported by the build script of libjulius-sys
+    let include_dirs = std::env::var_os("DEP_JULIUS_INCLUDE")
+        .ok_or("libjulius-sys did not report its include directories")?;
+    cc::Build::new()
+        .includes(std::env::split_paths(&include_dirs))
+        .file("layout.c")
+        .warnings(false)
+        .cargo_warnings(false)
+        .try_compile("julius_layout")?;
+    Ok(())
+}
@@ -0,0 +1,77 @@
+/* Sizes and offsets of libjulius structures as seen by the C compiler,
+   compared with the Rust bindings by tests/layout.rs. Kept out of libjulius-sys so that
+   the probe is not linked into programs. */
+
+#include <stddef.h>
+#include <string.h>
+#include <julius/julius.h>
+
+#define SIZE(type) { #type, sizeof(type) }
+#define OFFSET(type, field) { #type "." #field, offsetof(type, field) }
+
+static const struct {
+  const char *name;
+  size_t value;
+} layout[] = {
+  SIZE(WORD_ID),
+  SIZE(LOGPROB),
+  SIZE(boolean),
+  SIZE(WORD_INFO),
+  SIZE(Jconf),
+  SIZE(JCONF_SEARCH),
+  SIZE(ADIn),
+  SIZE(MFCCCalc),
+  SIZE(GMMCalc),
+  SIZE(Sentence),
+  SIZE(SentenceAlign),
+  SIZE(WordGraph),
+  SIZE(CN_CLUSTER),
+  SIZE(Output),
+  SIZE(RecogProcess),
+  SIZE(Recog),
+  OFFSET(WORD_INFO, wname),
+  OFFSET(WORD_INFO, woutput),
+  OFFSET(WORD_INFO, num),
+  OFFSET(WORD_INFO, head_silwid),
+  OFFSET(WORD_INFO, tail_silwid),
+  OFFSET(WORD_INFO, is_transparent),
+  OFFSET(ADIn, adin_cut_on),
+  OFFSET(ADIn, total_captured_len),
+  OFFSET(ADIn, last_trigger_sample),
+  OFFSET(ADIn, last_trigger_len),
+  OFFSET(ADIn, current_input_name),
+  OFFSET(Sentence, word),
+  OFFSET(Sentence, word_num),
+  OFFSET(Sentence, confidence),
+  OFFSET(Sentence, score),
+  OFFSET(Sentence, align),
+  OFFSET(Output, status),
+  OFFSET(Output, num_frame),
+  OFFSET(Output, sent),
+  OFFSET(Output, wg),
+  OFFSET(Output, confnet),
+  OFFSET(Output, pass1),
+  OFFSET(RecogProcess, live),
+  OFFSET(RecogProcess, result),
+  OFFSET(RecogProcess, pass1_wseq),
+  OFFSET(RecogProcess, pass1_score),
+  OFFSET(Recog, jconf),
+  OFFSET(Recog, adin),
+  OFFSET(Recog, mfcclist),
+  OFFSET(Recog, process_list),
+  OFFSET(Recog, gc),
+  OFFSET(Recog, callback_function),
+  OFFSET(Recog, hook),
+};
+
+/* Returns the size or offset named as in the table, or (size_t)-1 if unknown. */
+size_t
+libjulius_sys_layout(const char *name)
+{
+  size_t i;
+
+  for (i = 0; i < sizeof(layout) / sizeof(layout[0]); i++) {
+    if (strcmp(layout[i].name, name) == 0) return layout[i].value;
+  }
+  return (size_t)-1;
+}
@@ -0,0 +1,16 @@
+//! Sizes and offsets of the libjulius structures as seen by the C compiler, for the layout
+//! tests of the `libjulius-sys` bindings (`cargo test -p libjulius-sys-layout`).
+
+use std::ffi::{c_char, CString};
+
+extern "C" {
+    fn libjulius_sys_layout(name: *const c_char) -> usize;
+}
+
+/// Returns the size of a type (e.g. `Recog`) or the offset of a field (e.g. `Recog.adin`),
+/// or `None` if `layout.c` does not list it.
+pub fn layout(name: &str) -> Option<usize> {
+    let name = CString::new(name).ok()?;
+    let value = unsafe { libjulius_sys_layout(name.as_ptr()) };
+    (value != usize::MAX).then_some(value)
+}
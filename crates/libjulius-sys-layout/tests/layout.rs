@@ -0,0 +1,84 @@
+//! Compares the layout of the bindings with the C compiler, which sees the configuration
+//! libjulius is actually compiled with.
+
+use std::mem::{offset_of, size_of};
+
+use libjulius_sys::*;
+
+fn c_layout(name: &str) -> usize {
+    libjulius_sys_layout::layout(name).unwrap_or_else(|| panic!("{} is not in layout.c", name))
+}
+
+macro_rules! assert_size {
+    ($($ty:ident),* $(,)?) => {
+        $(assert_eq!(size_of::<$ty>(), c_layout(stringify!($ty)), "Size of {}", stringify!($ty));)*
+    };
+}
+
+macro_rules! assert_offset {
+    ($ty:ident, $($field:ident),* $(,)?) => {
+        $(assert_eq!(
+            offset_of!($ty, $field),
+            c_layout(concat!(stringify!($ty), ".", stringify!($field))),
+            "Offset of {}.{}",
+            stringify!($ty),
+            stringify!($field),
+        );)*
+    };
+}
+
+#[test]
+fn sizes() {
+    assert_size!(
+        WORD_ID,
+        LOGPROB,
+        boolean,
+        WORD_INFO,
+        Jconf,
+        JCONF_SEARCH,
+        ADIn,
+        MFCCCalc,
+        GMMCalc,
+        Sentence,
+        SentenceAlign,
+        WordGraph,
+        CN_CLUSTER,
+        Output,
+        RecogProcess,
+        Recog,
+    );
+}
+
+#[test]
+fn offsets() {
+    assert_offset!(
+        WORD_INFO,
+        wname,
+        woutput,
+        num,
+        head_silwid,
+        tail_silwid,
+        is_transparent,
+    );
+    assert_offset!(
+        ADIn,
+        adin_cut_on,
+        total_captured_len,
+        last_trigger_sample,
+        last_trigger_len,
+        current_input_name,
+    );
+    assert_offset!(Sentence, word, word_num, confidence, score, align);
+    assert_offset!(Output, status, num_frame, sent, wg, confnet, pass1);
+    assert_offset!(RecogProcess, live, result, pass1_wseq, pass1_score);
+    assert_offset!(
+        Recog,
+        jconf,
+        adin,
+        mfcclist,
+        process_list,
+        gc,
+        callback_function,
+        hook,
+    );
+}
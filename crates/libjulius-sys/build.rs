@@ -1,9 +1,10 @@
 use std::{
+    collections::{hash_map::DefaultHasher, BTreeSet},
     error::Error,
     fs::File,
-    io::Write,
+    hash::{Hash, Hasher},
     path::{Path, PathBuf},
-    process::{Command, Stdio},
+    process::Command,
 };
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -12,113 +13,285 @@ fn main() -> Result<(), Box<dyn Error>> {
     let build_dir_str = std::env::var_os("OUT_DIR").unwrap();
     let build_dir = Path::new(&build_dir_str);
 
-    let julius_dir = prepare_source(build_dir)?;
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=source.patch");
 
-    eprintln!("--- patch start ---");
-
-    let patch_config = Command::new("patch")
-        .arg("-p")
-        .arg("1")
-        .arg("-d")
-        .arg(&julius_dir)
-        .arg("-i")
-        .arg(source_dir.join("configure.patch"))
-        .stdin(Stdio::piped())
-        // .stdout(Stdio::null())
-        .output()?;
-
-    eprintln!("{}\n", std::str::from_utf8(&patch_config.stdout)?);
-    eprintln!("{}\n", std::str::from_utf8(&patch_config.stderr)?);
-
-    let patch_source = Command::new("patch")
-        .arg("-p")
-        .arg("1")
-        .arg("-d")
-        .arg(&julius_dir)
-        .arg("-i")
-        .arg(source_dir.join("source.patch"))
-        .stdin(Stdio::piped())
-        // .stdout(Stdio::null())
-        .output()?;
+    println!("cargo:rerun-if-changed=wrapper.h");
+    println!("cargo:rustc-check-cfg=cfg(generated_bindings)");
+    println!("cargo:rustc-check-cfg=cfg(system_library)");
+
+    println!("cargo:rerun-if-env-changed=JULIUS_SYSTEM");
+    if cfg!(feature = "system") || std::env::var_os("JULIUS_SYSTEM").is_some_and(|v| v == "1") {
+        // The configuration of the installed library is unknown
+        println!("cargo:rustc-cfg=system_library");
+        let include_dirs = link_system()?;
+        export_include_dirs(&include_dirs)?;
+        return generate_bindings(&include_dirs, build_dir);
+    }
 
-    eprintln!("{}\n", std::str::from_utf8(&patch_source.stdout)?);
-    eprintln!("{}\n", std::str::from_utf8(&patch_source.stderr)?);
+    let julius_dir = prepare_source(build_dir, &source_dir.join("source.patch"))?;
 
-    eprintln!("--- patch end ---");
+    let config = Config::new()?;
+    println!("cargo:rerun-if-env-changed=JULIUS_CHECK_CONFIGURE");
+    if std::env::var_os("JULIUS_CHECK_CONFIGURE").is_some_and(|v| v == "1") {
+        config.check_configure(&julius_dir)?;
+    }
+    config.write(&julius_dir)?;
+    let include_dirs = compile(&julius_dir, &config)?;
+    export_include_dirs(&include_dirs)?;
+
+    println!("cargo:rerun-if-changed=src/bindings.rs");
+    println!("cargo:rerun-if-env-changed=JULIUS_REQUIRE_PREGENERATED");
+    let pregenerated = config.is_pregenerated()?;
+    // Set in CI, so that the checked-in bindings keep following the default features
+    if !pregenerated && std::env::var_os("JULIUS_REQUIRE_PREGENERATED").is_some_and(|v| v == "1") {
+        return Err(
+            "The config.h flags differ from src/bindings.rs, see regenerate-bindings.sh".into(),
+        );
+    }
+    if cfg!(feature = "generate-bindings") || !pregenerated {
+        generate_bindings(&include_dirs, build_dir)?;
+    }
 
-    eprintln!("--- configure start ---");
+    Ok(())
+}
 
-    let configure_output = Command::new("./configure")
-        .arg("--enable-words-int")
-        .arg("--with-mictype=none")
-        .current_dir(&julius_dir)
-        .output()?;
+/// Generates the bindings into `OUT_DIR`, which are used instead of the pregenerated
+/// `src/bindings.rs` with the `generated_bindings` cfg.
+///
+/// The API of libfvad is declared by hand in `src/fvad.rs` instead.
+fn generate_bindings(include_dirs: &[PathBuf], build_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let bindings = bindgen::Builder::default()
+        .header("wrapper.h")
+        .clang_args(
+            include_dirs
+                .iter()
+                .map(|dir| format!("-I{}", dir.display())),
+        )
+        .allowlist_file(".*/(julius|sent)/[^/]*\\.h")
+        .generate()
+        .map_err(|e| format!("Unable to generate bindings: {}", e))?;
+    bindings.write_to_file(build_dir.join("bindings.rs"))?;
 
-    eprintln!("{}\n", std::str::from_utf8(&configure_output.stdout)?);
-    eprintln!("{}\n", std::str::from_utf8(&configure_output.stderr)?);
+    println!("cargo:rustc-cfg=generated_bindings");
+    Ok(())
+}
 
-    eprintln!("--- configure end ---");
+/// Passes the include directories to the build scripts of dependents as `DEP_JULIUS_INCLUDE`,
+/// e.g. for the layout tests in `libjulius-sys-layout`.
+fn export_include_dirs(include_dirs: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+    let joined = std::env::join_paths(include_dirs)?;
+    println!("cargo:include={}", joined.to_string_lossy());
+    Ok(())
+}
 
-    eprintln!("--- make start ---");
+/// Links libjulius and libsent installed on the system, found by pkg-config or by
+/// `libjulius-config` and `libsent-config`.
+/// Returns the include directories.
+fn link_system() -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let library = match probe_pkg_config() {
+        Ok(library) => library,
+        Err(pkg_config_error) => probe_config_scripts().map_err(|e| {
+            format!(
+                "No system libjulius found. pkg-config: {}; libjulius-config: {}",
+                pkg_config_error, e
+            )
+        })?,
+    };
+
+    let callback_h = library
+        .include_dirs
+        .iter()
+        .map(PathBuf::as_path)
+        .chain([Path::new("/usr/local/include"), Path::new("/usr/include")])
+        .map(|dir| dir.join("julius/callback.h"))
+        .find(|path| path.exists())
+        .ok_or("julius/callback.h of the system libjulius is not found")?;
+    if !std::fs::read_to_string(&callback_h)?.contains("CALLBACK_ADIN_INJECT") {
+        return Err(format!(
+            "{} has no CALLBACK_ADIN_INJECT. Rebuild libjulius with source.patch applied",
+            callback_h.display()
+        )
+        .into());
+    }
 
-    let make_output = Command::new("make").current_dir(&julius_dir).output()?;
-    eprintln!("{}\n", std::str::from_utf8(&make_output.stdout)?);
-    eprintln!("{}\n", std::str::from_utf8(&make_output.stderr)?);
+    let mut library = library;
+    if cfg!(feature = "fvad") {
+        // libfvad bundled with Julius is not installed with libjulius
+        let fvad = pkg_config::Config::new()
+            .cargo_metadata(false)
+            .probe("libfvad")
+            .map_err(|e| {
+                format!(
+                    "The `fvad` feature needs libfvad installed on the system, \
+                     or disable the feature. pkg-config: {}",
+                    e
+                )
+            })?;
+        library.include_dirs.extend(fvad.include_paths);
+        library.link_dirs.extend(fvad.link_paths);
+        library.libs.extend(fvad.libs);
+    }
 
-    eprintln!("--- make end ---");
+    for dir in library.link_dirs {
+        println!("cargo:rustc-link-search=native={}", dir.display());
+    }
+    for lib in library.libs {
+        println!("cargo:rustc-link-lib={}", lib);
+    }
+    Ok(library.include_dirs)
+}
 
-    println!(
-        "cargo:rustc-link-search={}",
-        julius_dir.join("libjulius").to_str().unwrap()
-    );
-    println!(
-        "cargo:rustc-link-search={}",
-        julius_dir.join("libsent").to_str().unwrap()
-    );
+#[derive(Default)]
+struct SystemLibrary {
+    include_dirs: Vec<PathBuf>,
+    link_dirs: Vec<PathBuf>,
+    libs: Vec<String>,
+}
 
-    println!("cargo:rustc-link-lib=static=julius");
-    println!("cargo:rustc-link-lib=static=sent");
-    println!("cargo:rustc-link-lib=dylib=z");
-    println!("cargo:rustc-link-lib=dylib=gomp");
-    println!("cargo:rustc-link-lib=dylib=sndfile");
+fn probe_pkg_config() -> Result<SystemLibrary, Box<dyn Error>> {
+    let mut library = SystemLibrary::default();
+    for name in ["julius", "sent"] {
+        let found = pkg_config::Config::new()
+            .cargo_metadata(false)
+            .probe(name)?;
+        check_version(name, &found.version)?;
+        library.include_dirs.extend(found.include_paths);
+        library.link_dirs.extend(found.link_paths);
+        library.libs.extend(found.libs);
+    }
+    Ok(library)
+}
 
-    generate_bindings(julius_dir.as_path());
+fn probe_config_scripts() -> Result<SystemLibrary, Box<dyn Error>> {
+    let run = |script: &str, arg: &str| -> Result<String, Box<dyn Error>> {
+        let output = Command::new(script)
+            .arg(arg)
+            .output()
+            .map_err(|e| format!("Failed to run {}: {}", script, e))?;
+        if !output.status.success() {
+            return Err(format!("{} {} failed", script, arg).into());
+        }
+        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+    };
+
+    let mut library = SystemLibrary::default();
+    for script in ["libjulius-config", "libsent-config"] {
+        check_version(script, &run(script, "--version")?)?;
+        let flags = run(script, "--cflags")? + " " + &run(script, "--libs")?;
+        for flag in flags.split_whitespace() {
+            if let Some(dir) = flag.strip_prefix("-I") {
+                library.include_dirs.push(dir.into());
+            } else if let Some(dir) = flag.strip_prefix("-L") {
+                library.link_dirs.push(dir.into());
+            } else if let Some(lib) = flag.strip_prefix("-l") {
+                library.libs.push(lib.to_string());
+            }
+        }
+    }
+    Ok(library)
+}
 
+/// Accepts the version of the bindings, e.g. `4.6` or `4.6.1`.
+fn check_version(name: &str, version: &str) -> Result<(), Box<dyn Error>> {
+    let version = version.trim();
+    let supported = version
+        .strip_prefix(JULIUS_VERSION)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'));
+    if !supported {
+        return Err(format!(
+            "{} is version {}, but the bindings are for Julius {}",
+            name, version, JULIUS_VERSION
+        )
+        .into());
+    }
     Ok(())
 }
 
-#[cfg(not(feature = "generate-bindings"))]
-fn generate_bindings(_julius_dir: &Path) {}
+/// Version of the Julius source.
+const JULIUS_VERSION: &str = "4.6";
+/// Name of the source directory, as in the release tarball.
+const JULIUS_DIR_NAME: &str = "julius-4.6";
 
-#[cfg(feature = "generate-bindings")]
-fn generate_bindings(julius_dir: &Path) {
-    let bindings = bindgen::Builder::default()
-        .header("wrapper.h")
-        .clang_args([
-            format!(
-                "-F{}",
-                julius_dir.join("libjulius/include").to_str().unwrap()
-            ),
-            format!("-F{}", julius_dir.join("libsent/include").to_str().unwrap()),
-        ])
-        .allowlist_file(format!("{}.*", julius_dir.to_str().unwrap()))
-        .parse_callbacks(Box::new(bindgen::CargoCallbacks))
-        .generate()
-        .expect("Unable to generate bindings");
+/// Where the Julius source is taken from.
+enum Origin {
+    Dir(PathBuf),
+    Tarball(PathBuf),
+}
 
-    // Write the bindings to the $OUT_DIR/bindings.rs file.
-    let out_path = PathBuf::from("src");
-    bindings
-        .write_to_file(out_path.join("bindings.rs"))
-        .expect("Couldn't write bindings!");
+/// Name of the file recording the origin of the source in the build directory.
+const STAMP_NAME: &str = "julius-source.stamp";
+
+/// Places the patched Julius source in the build directory, looking in order at
+/// `JULIUS_SOURCE_DIR`, `JULIUS_TARBALL`, the `libjulius-src` crate and the GitHub release.
+///
+/// An existing copy is reused while the stamp of its origin and of `patch` is unchanged.
+fn prepare_source(build_dir: &Path, patch: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    println!("cargo:rerun-if-env-changed=JULIUS_SOURCE_DIR");
+    println!("cargo:rerun-if-env-changed=JULIUS_TARBALL");
+
+    let origin = if let Some(dir) = std::env::var_os("JULIUS_SOURCE_DIR") {
+        Origin::Dir(dir.into())
+    } else if let Some(tarball) = std::env::var_os("JULIUS_TARBALL") {
+        Origin::Tarball(tarball.into())
+    } else if let Some(dir) = libjulius_src::source_dir() {
+        Origin::Dir(dir)
+    } else {
+        Origin::Tarball(download(build_dir)?)
+    };
+    let (Origin::Dir(path) | Origin::Tarball(path)) = &origin;
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    let mut hasher = DefaultHasher::new();
+    fingerprint(path, &mut hasher)?;
+    std::fs::read(patch)?.hash(&mut hasher);
+    let stamp = format!("{}\n{:016x}\n", path.display(), hasher.finish());
+
+    let julius_dir = build_dir.join(JULIUS_DIR_NAME);
+    let stamp_path = build_dir.join(STAMP_NAME);
+    if julius_dir.exists() && std::fs::read_to_string(&stamp_path).is_ok_and(|s| s == stamp) {
+        return Ok(julius_dir);
+    }
 
-    println!("cargo:rerun-if-changed=wrapper.h");
+    if stamp_path.exists() {
+        std::fs::remove_file(&stamp_path)?;
+    }
+    if julius_dir.exists() {
+        std::fs::remove_dir_all(&julius_dir)?;
+    }
+    match &origin {
+        Origin::Dir(dir) => copy_dir(dir, &julius_dir)?,
+        Origin::Tarball(tarball) => unpack(tarball, build_dir)?,
+    }
+    libjulius_src::apply_patch(&julius_dir, &std::fs::read_to_string(patch)?)
+        .map_err(|e| format!("Failed to apply {}: {}", patch.display(), e))?;
+    std::fs::write(stamp_path, stamp)?;
+    Ok(julius_dir)
 }
 
-fn prepare_source(build_dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
-    use flate2::read::GzDecoder;
-    use std::io::Read;
-    use tar::Archive;
+/// Hashes the names, sizes and modification times of the files under `path`.
+fn fingerprint(path: &Path, hasher: &mut DefaultHasher) -> Result<(), Box<dyn Error>> {
+    let metadata =
+        std::fs::metadata(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if metadata.is_dir() {
+        let mut entries = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for entry in entries {
+            entry.file_name().hash(hasher);
+            fingerprint(&entry, hasher)?;
+        }
+    } else {
+        metadata.len().hash(hasher);
+        metadata.modified()?.hash(hasher);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "download")]
+fn download(build_dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    use std::io::Write;
 
     let file_name = "v4.6.tar.gz";
 
@@ -127,7 +300,6 @@ fn prepare_source(build_dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
 
     // Download source file to build directory
     if !source_path_for_build.exists() {
-        // copy(&source_path, &source_path_for_build)?;
         let tmp_path = build_dir.join(file_name.to_owned() + ".download");
 
         // Download a tarball
@@ -141,14 +313,542 @@ fn prepare_source(build_dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
         std::fs::rename(tmp_path, &source_path_for_build).expect("Failed to rename temporary file");
     }
 
-    // Decompress a tar.gz file
-    let mut tar_gz = File::open(source_path_for_build)?;
-    let mut buffer = Vec::new();
-    tar_gz.read_to_end(&mut buffer)?;
-    let cursor = std::io::Cursor::new(buffer);
-    let gzdecoder = GzDecoder::new(cursor);
-    let mut archive = Archive::new(gzdecoder);
+    Ok(source_path_for_build)
+}
+
+#[cfg(not(feature = "download"))]
+fn download(_build_dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    Err(
+        "Julius source not found. Set JULIUS_SOURCE_DIR or JULIUS_TARBALL, \
+         vendor it with crates/libjulius-src/vendor.sh, or enable the `download` feature"
+            .into(),
+    )
+}
+
+fn unpack(tarball: &Path, build_dir: &Path) -> Result<(), Box<dyn Error>> {
+    use flate2::read::GzDecoder;
+    use std::io::BufReader;
+    use tar::Archive;
+
+    let tar_gz = BufReader::new(File::open(tarball)?);
+    let mut archive = Archive::new(GzDecoder::new(tar_gz));
     archive.unpack(build_dir)?;
 
-    Ok(build_dir.join("julius-4.6"))
+    if !build_dir.join(JULIUS_DIR_NAME).exists() {
+        return Err(format!("{} does not contain {}", tarball.display(), JULIUS_DIR_NAME).into());
+    }
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            // Keeps the permissions, e.g. of configure
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Definitions of the generated `config.h` files and the sources and libraries they need,
+/// selected by the cargo features like the options of `configure`.
+struct Config {
+    julius: Vec<(&'static str, String)>,
+    sent: Vec<(&'static str, String)>,
+    /// Enabled SIMD variants of the DNN computation, e.g. `avx` for `calc_dnn_avx.c`.
+    simd: Vec<&'static str>,
+    /// Audio input drivers to compile.
+    mic_sources: Vec<&'static str>,
+    /// Libraries to link dynamically.
+    links: Vec<&'static str>,
+    /// Options of `configure` selecting the same configuration.
+    configure_args: Vec<String>,
+    fvad: bool,
+    openmp: bool,
+}
+
+impl Config {
+    fn new() -> Result<Self, Box<dyn Error>> {
+        let target = std::env::var("TARGET")?;
+        let target_arch = std::env::var("CARGO_CFG_TARGET_ARCH")?;
+        let target_os = std::env::var("CARGO_CFG_TARGET_OS")?;
+        let quote = |s: &str| format!("{:?}", s);
+        let defined = || "1".to_string();
+
+        let compiler = cc::Build::new().warnings(false).try_get_compiler()?;
+        let build_info = std::iter::once(compiler.path().as_os_str())
+            .chain(compiler.args().iter().map(|arg| arg.as_os_str()))
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut julius = vec![
+            ("JULIUS_PRODUCTNAME", quote("JuliusLib")),
+            ("JULIUS_VERSION", quote(JULIUS_VERSION)),
+            ("JULIUS_SETUP", quote("fast")),
+            ("JULIUS_HOSTINFO", quote(&target)),
+            ("JULIUS_BUILD_INFO", quote(&build_info)),
+            ("STDC_HEADERS", defined()),
+            ("HAVE_PTHREAD", defined()),
+            ("UNIGRAM_FACTORING", defined()),
+            ("LOWMEM2", defined()),
+            ("PASS1_IWCD", defined()),
+            ("SCAN_BEAM", defined()),
+            ("GPRUNE_DEFAULT_BEAM", defined()),
+            ("CONFIDENCE_MEASURE", defined()),
+            ("LM_FIX_DOUBLE_SCORING", defined()),
+            ("GRAPHOUT_DYNAMIC", defined()),
+            ("GRAPHOUT_SEARCH", defined()),
+            ("ENABLE_PLUGIN", defined()),
+        ];
+        let mut sent = vec![
+            ("LIBSENT_VERSION", quote(JULIUS_VERSION)),
+            ("STDC_HEADERS", defined()),
+            ("USE_ADDLOG_ARRAY", defined()),
+            ("HAVE_SOCKLEN_T", defined()),
+            ("HAVE_UNISTD_H", defined()),
+            ("HAVE_STRCASECMP", defined()),
+            ("HAVE_SLEEP", defined()),
+            ("CLASS_NGRAM", defined()),
+            ("MFCC_SINCOS_TABLE", defined()),
+        ];
+        let mut links = vec![];
+        // plugin.c loads plugins with dlopen, which is in libc elsewhere
+        if target_os == "linux" {
+            links.push("dl");
+        }
+
+        let mut configure_args = vec![];
+        for (enabled, option) in [
+            (cfg!(feature = "words-int"), "words-int"),
+            (cfg!(feature = "mbr"), "mbr"),
+            (cfg!(feature = "fvad"), "fvad"),
+            (cfg!(feature = "gmm-vad"), "gmm-vad"),
+            (cfg!(feature = "decoder-vad"), "decoder-vad"),
+            (cfg!(feature = "zlib"), "zlib"),
+        ] {
+            let action = if enabled { "enable" } else { "disable" };
+            configure_args.push(format!("--{}-{}", action, option));
+        }
+
+        // --enable-words-int, --enable-mbr
+        if cfg!(feature = "words-int") {
+            sent.push(("WORDS_INT", defined()));
+        }
+        if cfg!(feature = "mbr") {
+            sent.push(("USE_MBR", defined()));
+        }
+
+        // --enable-fvad, --enable-gmm-vad, --enable-decoder-vad
+        if cfg!(feature = "fvad") {
+            julius.push(("HAVE_LIBFVAD", defined()));
+        }
+        if cfg!(feature = "gmm-vad") {
+            julius.push(("GMM_VAD", defined()));
+        }
+        if cfg!(feature = "decoder-vad") {
+            julius.push(("SPSEGMENT_NAIST", defined()));
+        }
+        if cfg!(any(feature = "gmm-vad", feature = "decoder-vad")) {
+            julius.push(("BACKEND_VAD", defined()));
+        }
+
+        // --with-mictype
+        let mut mic_sources = vec![];
+        if cfg!(any(feature = "alsa", feature = "pulseaudio")) {
+            sent.push(("USE_MIC", defined()));
+            mic_sources.push("adin_mic_linux.c");
+        }
+        if cfg!(feature = "alsa") {
+            sent.push(("HAS_ALSA", defined()));
+            mic_sources.push("adin_mic_linux_alsa.c");
+            links.push("asound");
+        }
+        if cfg!(feature = "pulseaudio") {
+            sent.push(("HAS_PULSEAUDIO", defined()));
+            mic_sources.push("adin_pulseaudio.c");
+            links.extend(["pulse-simple", "pulse"]);
+        }
+        // configure defines these whenever the headers exist, whatever the microphone
+        let build_dir = PathBuf::from(std::env::var_os("OUT_DIR").ok_or("OUT_DIR is not set")?);
+        for &(header, define) in PROBED_HEADERS {
+            if has_header(header, &build_dir) {
+                sent.push((define, defined()));
+            }
+        }
+        let (api_name, api_desc) = if cfg!(feature = "alsa") {
+            ("alsa", "Advanced Linux Sound Architecture")
+        } else if cfg!(feature = "pulseaudio") {
+            ("pulseaudio", "PulseAudio")
+        } else {
+            ("none", "")
+        };
+        if api_name != "none" {
+            configure_args.push(format!("--with-mictype={}", api_name));
+        }
+        sent.push(("AUDIO_API_NAME", quote(api_name)));
+        sent.push(("AUDIO_API_DESC", quote(api_desc)));
+
+        if cfg!(feature = "sndfile") {
+            sent.push((
+                "AUDIO_FORMAT_DESC",
+                quote("various formats by libsndfile ver.1"),
+            ));
+            sent.push(("HAVE_LIBSNDFILE", defined()));
+            sent.push(("HAVE_LIBSNDFILE_VER1", defined()));
+            links.push("sndfile");
+        } else {
+            sent.push(("AUDIO_FORMAT_DESC", quote("RAW and WAV only")));
+            configure_args.push("--without-sndfile".to_string());
+        }
+
+        // Compressed files are read by an external gzip without zlib
+        if cfg!(feature = "zlib") {
+            sent.push(("HAVE_ZLIB", defined()));
+            sent.push(("GZIP_READING_DESC", quote("zlib library")));
+            links.push("z");
+        } else {
+            sent.push(("GZIP_READING_DESC", quote("gzip program")));
+        }
+
+        // DNN-HMM is always built, the features select the SIMD variants
+        let mut simd = vec![];
+        for (enabled, variant, define, arch) in [
+            (cfg!(feature = "simd-sse"), "sse", "HAS_SIMD_SSE", "x86_64"),
+            (cfg!(feature = "simd-avx"), "avx", "HAS_SIMD_AVX", "x86_64"),
+            (cfg!(feature = "simd-fma"), "fma", "HAS_SIMD_FMA", "x86_64"),
+            (
+                cfg!(feature = "simd-neon"),
+                "neon",
+                "HAS_SIMD_NEON",
+                "aarch64",
+            ),
+            (
+                cfg!(feature = "simd-neon"),
+                "neonv2",
+                "HAS_SIMD_NEONV2",
+                "aarch64",
+            ),
+        ] {
+            if enabled && target_arch == arch {
+                sent.push((define, defined()));
+                simd.push(variant);
+            }
+        }
+
+        let openmp = cfg!(feature = "openmp");
+        if openmp {
+            links.push(if compiler.is_like_clang() {
+                "omp"
+            } else {
+                "gomp"
+            });
+        }
+
+        Ok(Self {
+            julius,
+            sent,
+            simd,
+            mic_sources,
+            links,
+            configure_args,
+            fvad: cfg!(feature = "fvad"),
+            openmp,
+        })
+    }
+
+    /// Returns whether `src/bindings.rs` is generated with this configuration, i.e. the flags
+    /// of the `config.h` files are those found at its top.
+    ///
+    /// Any flag may change the structures, so the bindings are generated for any other set of
+    /// features. The flags of `PROBED_HEADERS` are ignored.
+    fn is_pregenerated(&self) -> Result<bool, Box<dyn Error>> {
+        let mut flags: Vec<&str> = self
+            .julius
+            .iter()
+            .chain(&self.sent)
+            .filter(|(name, value)| value == "1" && !is_probed(name))
+            .map(|(name, _)| *name)
+            .collect();
+        flags.sort_unstable();
+        flags.dedup();
+        Ok(flags == pregenerated_flags()?)
+    }
+
+    /// Writes `julius/config.h` and `sent/config.h`, which `configure` would generate.
+    fn write(&self, julius_dir: &Path) -> Result<(), Box<dyn Error>> {
+        for (header, defines) in [
+            ("libjulius/include/julius/config.h", &self.julius),
+            ("libsent/include/sent/config.h", &self.sent),
+        ] {
+            let mut text = String::from("/* Generated by the build script of libjulius-sys */\n");
+            for (name, value) in defines {
+                text += &format!("#define {} {}\n", name, value);
+            }
+            std::fs::write(julius_dir.join(header), text)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `configure` in `julius_dir` with the matching options and fails if the names it
+    /// defines in the `config.h` files differ from ours.
+    ///
+    /// `configure` probes the system, so the microphone driver without a mic feature, the
+    /// SIMD variants and the libraries found can legitimately differ; the report lists them.
+    fn check_configure(&self, julius_dir: &Path) -> Result<(), Box<dyn Error>> {
+        let compiler = cc::Build::new().warnings(false).try_get_compiler()?;
+        let status = Command::new("sh")
+            .arg("configure")
+            .args(&self.configure_args)
+            .env("CC", compiler.path())
+            .current_dir(julius_dir)
+            .stdout(std::process::Stdio::null())
+            .status()
+            .map_err(|e| format!("Failed to run configure: {}", e))?;
+        if !status.success() {
+            return Err(format!("configure {} failed", self.configure_args.join(" ")).into());
+        }
+
+        let mut differences = vec![];
+        for (header, defines) in [
+            ("libjulius/include/julius/config.h", &self.julius),
+            ("libsent/include/sent/config.h", &self.sent),
+        ] {
+            let text = std::fs::read_to_string(julius_dir.join(header))?;
+            let configured: BTreeSet<&str> = text
+                .lines()
+                .filter_map(|line| line.trim().strip_prefix("#define "))
+                .filter_map(|rest| rest.split_whitespace().next())
+                .collect();
+            let generated: BTreeSet<&str> = defines.iter().map(|(name, _)| *name).collect();
+            for name in configured.difference(&generated) {
+                differences.push(format!("{}: {} only by configure", header, name));
+            }
+            for name in generated.difference(&configured) {
+                differences.push(format!("{}: {} only by the build script", header, name));
+            }
+        }
+        if !differences.is_empty() {
+            for difference in &differences {
+                eprintln!("{}", difference);
+            }
+            return Err(format!(
+                "config.h differs from configure {} in {} names, listed above",
+                self.configure_args.join(" "),
+                differences.len()
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Compiler flags of the SIMD variant in `file`, or `None` if it is not a SIMD variant.
+    /// Disabled variants are skipped with `Some(None)`.
+    fn simd_flags(&self, file: &Path) -> Option<Option<&'static [&'static str]>> {
+        let stem = file.file_stem()?.to_str()?;
+        let (_, variant) = stem.rsplit_once('_')?;
+        let flags: &[&str] = match variant {
+            "sse" => &["-msse4.1"],
+            "avx" => &["-mavx"],
+            "fma" => &["-mavx", "-mfma"],
+            "neon" | "neonv2" => &[],
+            _ => return None,
+        };
+        Some(self.simd.contains(&variant).then_some(flags))
+    }
+
+    /// Returns whether the audio input source `name` in `libsent/src/adin` is compiled.
+    fn adin_enabled(&self, name: &str) -> bool {
+        let is_mic = MIC_SOURCES.iter().any(|prefix| name.starts_with(prefix));
+        !is_mic || self.mic_sources.contains(&name)
+    }
+
+    /// Links the libraries the enabled options need.
+    fn link(&self) {
+        for lib in &self.links {
+            println!("cargo:rustc-link-lib=dylib={}", lib);
+        }
+    }
+}
+
+/// Returns the flags defined in the `config.h` files `src/bindings.rs` is generated with,
+/// sorted.
+///
+/// They are the constants of value 1 in the block of `config.h` at its top, which ends at the
+/// first constant that is neither a flag nor a string, `LM_UNDEF`.
+fn pregenerated_flags() -> Result<Vec<String>, Box<dyn Error>> {
+    let bindings = std::fs::read_to_string("src/bindings.rs")?;
+    let mut flags = vec![];
+    for line in bindings
+        .lines()
+        .filter(|line| line.starts_with("pub const "))
+    {
+        let Some((name, value)) = line["pub const ".len()..].split_once(": ") else {
+            break;
+        };
+        if value == "u32 = 1;" {
+            if !is_probed(name) {
+                flags.push(name.to_string());
+            }
+        } else if !value.starts_with("&[u8; ") {
+            break;
+        }
+    }
+    if flags.is_empty() {
+        return Err("No config.h flags found in src/bindings.rs".into());
+    }
+    flags.sort_unstable();
+    Ok(flags)
+}
+
+/// Headers `configure` checks for, and the flags it defines when they are found.
+const PROBED_HEADERS: &[(&str, &str)] = &[
+    ("sys/soundcard.h", "HAVE_SYS_SOUNDCARD_H"),
+    ("alsa/asoundlib.h", "HAVE_ALSA_ASOUNDLIB_H"),
+    ("pulse/simple.h", "HAVE_PULSE_SIMPLE_H"),
+];
+
+/// Returns whether `flag` is one of `PROBED_HEADERS`, which only select includes of the
+/// microphone sources and differ from host to host, so they are left out of the comparison
+/// with the pregenerated bindings.
+fn is_probed(flag: &str) -> bool {
+    PROBED_HEADERS.iter().any(|&(_, define)| define == flag)
+}
+
+/// Returns whether the C compiler finds `header`, like `AC_CHECK_HEADERS`.
+fn has_header(header: &str, build_dir: &Path) -> bool {
+    let probe = build_dir.join(format!("probe_{}.c", header.replace(['/', '.'], "_")));
+    std::fs::write(&probe, format!("#include <{}>\n", header)).is_ok()
+        && cc::Build::new()
+            .warnings(false)
+            .cargo_metadata(false)
+            .cargo_warnings(false)
+            .file(&probe)
+            .try_expand()
+            .is_ok()
+}
+
+/// Audio input drivers, compiled only when selected by a feature.
+const MIC_SOURCES: &[&str] = &[
+    "adin_mic_",
+    "adin_portaudio",
+    "adin_pulseaudio",
+    "adin_esd",
+    "adin_netaudio",
+    "adin_na",
+    "adin_oss",
+    "adin_alsa",
+];
+
+/// Compiles libsent, libjulius and the bundled libfvad into static libraries, and returns
+/// the include directories.
+fn compile(julius_dir: &Path, config: &Config) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let fvad_dir = if config.fvad {
+        let fvad_include = find_file(julius_dir, "fvad.h")?
+            .and_then(|header| Some(header.parent()?.to_path_buf()))
+            .ok_or("libfvad is not found in the Julius source")?;
+        Some(fvad_include)
+    } else {
+        None
+    };
+
+    let base = || {
+        let mut build = cc::Build::new();
+        build
+            .include(julius_dir.join("libsent/include"))
+            .include(julius_dir.join("libjulius/include"))
+            .warnings(false)
+            .cargo_warnings(false);
+        if let Some(fvad_include) = &fvad_dir {
+            build.include(fvad_include);
+        }
+        if config.openmp {
+            build.flag("-fopenmp");
+        }
+        build
+    };
+
+    if let Some(fvad_root) = fvad_dir.as_deref().and_then(Path::parent) {
+        let mut fvad = base();
+        fvad.include(fvad_root.join("src"))
+            .files(c_sources(&fvad_root.join("src"))?)
+            .try_compile("fvad")?;
+    }
+
+    let mut sent = base();
+    for file in c_sources(&julius_dir.join("libsent/src"))? {
+        let excluded = file.parent().and_then(Path::file_name) == Some("adin".as_ref())
+            && file
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| !config.adin_enabled(name));
+        if excluded {
+            continue;
+        }
+        match config.simd_flags(&file) {
+            None => {
+                sent.file(file);
+            }
+            Some(None) => {}
+            Some(Some(flags)) => {
+                let mut simd = base();
+                for flag in flags {
+                    simd.flag(flag);
+                }
+                for object in simd.file(&file).try_compile_intermediates()? {
+                    sent.object(object);
+                }
+            }
+        }
+    }
+    sent.try_compile("sent")?;
+
+    base()
+        .files(c_sources(&julius_dir.join("libjulius/src"))?)
+        .try_compile("julius")?;
+
+    config.link();
+
+    let mut include_dirs = vec![
+        julius_dir.join("libsent/include"),
+        julius_dir.join("libjulius/include"),
+    ];
+    include_dirs.extend(fvad_dir);
+    Ok(include_dirs)
+}
+
+/// Returns the C source files under `dir`, skipping the bundled PortAudio.
+fn c_sources(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if path.file_name() != Some("pa".as_ref()) {
+                files.extend(c_sources(&path)?);
+            }
+        } else if path.extension() == Some("c".as_ref()) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn find_file(dir: &Path, name: &str) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if let Some(found) = find_file(&path, name)? {
+                return Ok(Some(found));
+            }
+        } else if path.file_name() == Some(name.as_ref()) {
+            return Ok(Some(path));
+        }
+    }
+    Ok(None)
 }
@@ -0,0 +1,10 @@
+#!/bin/sh
+# Regenerates src/bindings.rs with the default features, which needs libclang.
+# Run it whenever the default features or the config.h flags they define change.
+set -eu
+cd "$(dirname "$0")"
+
+target=$(mktemp -d)
+trap 'rm -rf "$target"' EXIT
+CARGO_TARGET_DIR="$target" cargo build -p libjulius-sys --features generate-bindings
+cp "$(find "$target" -path '*/libjulius-sys-*/out/bindings.rs' | head -n 1)" src/bindings.rs
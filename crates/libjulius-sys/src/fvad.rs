@@ -0,0 +1,22 @@
+//! API of libfvad in `fvad.h`, declared by hand so that the bindings of libjulius do not depend
+//! on the `fvad` feature.
+
+/// Opaque instance of the detector.
+#[repr(C)]
+#[derive(Debug)]
+pub struct Fvad {
+    _unused: [u8; 0],
+}
+
+extern "C" {
+    pub fn fvad_new() -> *mut Fvad;
+    pub fn fvad_free(inst: *mut Fvad);
+    pub fn fvad_reset(inst: *mut Fvad);
+    pub fn fvad_set_mode(inst: *mut Fvad, mode: ::std::os::raw::c_int) -> ::std::os::raw::c_int;
+    pub fn fvad_set_sample_rate(
+        inst: *mut Fvad,
+        sample_rate: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+    pub fn fvad_process(inst: *mut Fvad, frame: *const i16, length: usize)
+        -> ::std::os::raw::c_int;
+}
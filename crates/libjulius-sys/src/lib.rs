@@ -1,4 +1,19 @@
 #![allow(non_upper_case_globals)]
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
+#[cfg(not(generated_bindings))]
 include!("./bindings.rs");
+#[cfg(generated_bindings)]
+include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+#[cfg(feature = "fvad")]
+mod fvad;
+#[cfg(feature = "fvad")]
+pub use fvad::*;
+
+// The bindings must follow the features libjulius is compiled with
+#[cfg(not(system_library))]
+const _: () = assert!(
+    std::mem::size_of::<WORD_ID>() == if cfg!(feature = "words-int") { 4 } else { 2 },
+    "WORD_ID of the bindings does not match the `words-int` feature"
+);